@@ -0,0 +1,8 @@
+use dobf::synth::table3_source;
+
+// Regenerates the checked-in table of src/synth/table3.rs:
+// cargo run --example gen_table3
+fn main() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/synth/table3.rs");
+    std::fs::write(path, table3_source()).expect("table3.rs should be writable");
+}
@@ -0,0 +1,643 @@
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::time::{Duration, Instant};
+
+use crate::error::{ArenaError, Error, Result};
+use crate::expr::arena::{Arena, Elem};
+use crate::expr::node::VarTerm;
+use crate::expr::utils::{compute_op, width_mask};
+use crate::reduce;
+use crate::rewrite::{cancel_sign, Pat, Rule};
+
+// Ops of any num of children, matched in any order
+const NARY_OPS: &str = "+.^&|";
+
+pub type Id = usize;
+
+// Node of the e-graph, its children being classes. Signs are unary ops
+// '-' and '~' of one child, the children of other ops are sorted.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ENode {
+    Var(String),
+    Op(char, Option<u64>, Vec<Id>),
+}
+
+// Num of arena elements of the node: signs are part of the node they apply
+// to, and cost nothing
+pub fn node_cost(node: &ENode) -> usize {
+    match node {
+        ENode::Op('-' | '~', _, _) => 0,
+        _ => 1,
+    }
+}
+
+pub struct SaturationConfig {
+    pub max_nodes: usize,
+    pub max_iters: usize,
+    pub time_limit: Duration,
+    // add the forms found by the linear MBA solver
+    pub linear_mba: bool,
+}
+
+impl Default for SaturationConfig {
+    fn default() -> Self {
+        SaturationConfig {
+            max_nodes: 10000,
+            max_iters: 30,
+            time_limit: Duration::from_secs(1),
+            linear_mba: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Saturated,
+    IterLimit,
+    NodeLimit,
+    TimeLimit,
+}
+
+#[derive(Debug)]
+pub struct SaturationReport {
+    pub stop: StopReason,
+    pub num_iters: usize,
+}
+
+// Pattern over the e-graph, the signs of a rewrite pattern become unary ops
+enum EPat {
+    Var(String),
+    Op(char, Option<u64>, Vec<EPat>),
+}
+
+impl EPat {
+    fn new(pat: &Pat, mask: u64) -> EPat {
+        let (sign, core) = match pat {
+            Pat::Var(name, sign) => (sign, EPat::Var(name.clone())),
+            Pat::Node(op, sign, cst, ch) => (
+                sign,
+                EPat::Op(
+                    *op,
+                    cst.map(|c| c & mask),
+                    ch.iter().map(|p| EPat::new(p, mask)).collect(),
+                ),
+            ),
+        };
+
+        // the innermost sign is the last one
+        sign.chars()
+            .rev()
+            .fold(core, |acc, s| EPat::Op(s, None, vec![acc]))
+    }
+}
+
+type EBinds = HashMap<String, Id>;
+
+// Match of a rule in a class. As in rewrite, an n-ary lhs may match part of
+// the children of a node, the rest (op, children, cst) being kept.
+struct EMatch {
+    class: Id,
+    binds: EBinds,
+    rest: Option<(char, Vec<Id>, Option<u64>)>,
+}
+
+// Classes of equivalent terms. Terms are only added, the original one is
+// never lost, and the cheapest term is extracted at the end.
+pub struct EGraph {
+    width: u8,
+    uf: Vec<Id>,
+    classes: Vec<Vec<ENode>>,
+    memo: HashMap<ENode, Id>,
+}
+
+impl EGraph {
+    pub fn new(width: u8) -> Self {
+        EGraph {
+            width,
+            uf: Vec::new(),
+            classes: Vec::new(),
+            memo: HashMap::new(),
+        }
+    }
+
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.memo.len()
+    }
+
+    pub fn num_classes(&self) -> usize {
+        self.class_ids().len()
+    }
+
+    pub fn find(&self, mut id: Id) -> Id {
+        while self.uf[id] != id {
+            id = self.uf[id];
+        }
+        id
+    }
+
+    // Nodes of the class of id
+    pub fn nodes(&self, id: Id) -> &[ENode] {
+        &self.classes[self.find(id)]
+    }
+
+    fn class_ids(&self) -> Vec<Id> {
+        (0..self.uf.len())
+            .filter(|&id| self.find(id) == id)
+            .collect()
+    }
+
+    fn canon(&self, node: &ENode) -> ENode {
+        match node {
+            ENode::Var(_) => node.clone(),
+            ENode::Op(op, cst, ch) => {
+                let mut ch: Vec<Id> = ch.iter().map(|&c| self.find(c)).collect();
+                if NARY_OPS.contains(*op) {
+                    ch.sort_unstable();
+                }
+                ENode::Op(*op, *cst, ch)
+            }
+        }
+    }
+
+    // Class of the node, a new one if the node is new
+    pub fn add(&mut self, node: ENode) -> Id {
+        let node = self.canon(&node);
+        if let Some(&id) = self.memo.get(&node) {
+            return self.find(id);
+        }
+
+        let id = self.uf.len();
+        self.uf.push(id);
+        self.classes.push(vec![node.clone()]);
+        self.memo.insert(node.clone(), id);
+
+        // "~~a" and "--a" are a
+        if let ENode::Op(s @ ('-' | '~'), None, ch) = &node {
+            let inner = self.classes[ch[0]].iter().find_map(|n| match n {
+                ENode::Op(in_s, None, in_ch) if in_s == s => Some(in_ch[0]),
+                _ => None,
+            });
+            if let Some(inner) = inner {
+                self.union(id, inner);
+            }
+        }
+
+        id
+    }
+
+    // Merge the classes of id1 and id2, rebuild must be called before the
+    // next search
+    pub fn union(&mut self, id1: Id, id2: Id) -> bool {
+        let (mut r1, mut r2) = (self.find(id1), self.find(id2));
+        if r1 == r2 {
+            return false;
+        }
+        if self.classes[r1].len() < self.classes[r2].len() {
+            mem::swap(&mut r1, &mut r2);
+        }
+
+        self.uf[r2] = r1;
+        let mut nodes = mem::take(&mut self.classes[r2]);
+        self.classes[r1].append(&mut nodes);
+        true
+    }
+
+    // Nodes are made canonical again, and classes holding the same node
+    // merged, until no more merge is found
+    pub fn rebuild(&mut self) {
+        loop {
+            let mut memo: HashMap<ENode, Id> = HashMap::new();
+            let mut to_union = Vec::new();
+            for id in self.class_ids() {
+                let mut nodes: Vec<ENode> =
+                    self.classes[id].iter().map(|n| self.canon(n)).collect();
+                nodes.sort();
+                nodes.dedup();
+                for n in nodes.iter() {
+                    match memo.get(n) {
+                        Some(&other) if other != id => to_union.push((other, id)),
+                        Some(_) => (),
+                        None => {
+                            memo.insert(n.clone(), id);
+                        }
+                    }
+                }
+                self.classes[id] = nodes;
+            }
+
+            if to_union.is_empty() {
+                self.memo = memo;
+                return;
+            }
+            for (id1, id2) in to_union {
+                self.union(id1, id2);
+            }
+        }
+    }
+
+    // Class of the expression at idx of arena, variables being matched by
+    // name
+    pub fn add_arena(&mut self, arena: &Arena, idx: usize) -> Result<Id> {
+        if arena.width() != self.width {
+            return Err(ArenaError::WrongWidth().into());
+        }
+        self.add_elem(arena, idx, &mut HashMap::new())
+    }
+
+    fn add_elem(&mut self, arena: &Arena, idx: usize, ids: &mut HashMap<usize, Id>) -> Result<Id> {
+        if let Some(&id) = ids.get(&idx) {
+            return Ok(id);
+        }
+
+        let (core, sign) = match arena.get(idx)? {
+            Elem::Leaf(l) => {
+                let name = arena.var_name(l.val.val)?.to_string();
+                (self.add(ENode::Var(name)), l.val.sign.clone())
+            }
+            _ => {
+                let (op, sign) = arena.copy_op_sign(idx)?;
+                let ch = arena
+                    .get_ch(idx)?
+                    .into_iter()
+                    .map(|ch_idx| self.add_elem(arena, ch_idx, ids))
+                    .collect::<Result<Vec<Id>>>()?;
+                (self.add(ENode::Op(op, arena.get_cst(idx)?, ch)), sign)
+            }
+        };
+        let id = sign
+            .chars()
+            .rev()
+            .fold(core, |acc, s| self.add(ENode::Op(s, None, vec![acc])));
+
+        ids.insert(idx, id);
+        Ok(id)
+    }
+
+    // Apply rules and, if set, the linear MBA solver, until nothing new is
+    // found or a limit is reached. The limits are checked between two
+    // applications, the e-graph is left consistent.
+    pub fn saturate(&mut self, rules: &[Rule], cfg: &SaturationConfig) -> Result<SaturationReport> {
+        let start = Instant::now();
+        let limit = |egraph: &EGraph| {
+            if egraph.num_nodes() >= cfg.max_nodes {
+                Some(StopReason::NodeLimit)
+            } else if start.elapsed() >= cfg.time_limit {
+                Some(StopReason::TimeLimit)
+            } else {
+                None
+            }
+        };
+
+        let mask = width_mask(self.width);
+        let pats: Vec<(EPat, EPat)> = rules
+            .iter()
+            .map(|r| (EPat::new(r.lhs_pat(), mask), EPat::new(r.rhs_pat(), mask)))
+            .collect();
+        let mut solved: HashSet<String> = HashSet::new();
+
+        self.rebuild();
+        let mut report = SaturationReport {
+            stop: StopReason::IterLimit,
+            num_iters: 0,
+        };
+        while report.num_iters < cfg.max_iters {
+            report.num_iters += 1;
+            let size = (self.num_nodes(), self.num_classes());
+
+            // all matches are found before any is applied
+            let mut matches = Vec::new();
+            for (rule_pos, (lhs, _)) in pats.iter().enumerate() {
+                for id in self.class_ids() {
+                    for m in self.match_rule(lhs, id) {
+                        matches.push((rule_pos, m));
+                    }
+                    if let Some(stop) = limit(self) {
+                        report.stop = stop;
+                        return Ok(report);
+                    }
+                }
+            }
+
+            for (rule_pos, m) in matches {
+                self.apply_match(&pats[rule_pos].1, &m)?;
+                if let Some(stop) = limit(self) {
+                    self.rebuild();
+                    report.stop = stop;
+                    return Ok(report);
+                }
+            }
+
+            if cfg.linear_mba {
+                self.rebuild();
+                self.solve_linear_mba(&mut solved)?;
+            }
+
+            self.rebuild();
+            if let Some(stop) = limit(self) {
+                report.stop = stop;
+                return Ok(report);
+            }
+            if (self.num_nodes(), self.num_classes()) == size {
+                report.stop = StopReason::Saturated;
+                return Ok(report);
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn match_rule(&self, lhs: &EPat, id: Id) -> Vec<EMatch> {
+        let (op, cst, ch) = match lhs {
+            EPat::Op(op, cst, ch) if NARY_OPS.contains(*op) => (*op, *cst, ch),
+            _ => {
+                return self
+                    .ematch(lhs, id, &EBinds::new())
+                    .into_iter()
+                    .map(|binds| EMatch {
+                        class: id,
+                        binds,
+                        rest: None,
+                    })
+                    .collect()
+            }
+        };
+
+        let mut res = Vec::new();
+        for node in self.classes[id].iter() {
+            let (n_cst, n_ch) = match node {
+                ENode::Op(n_op, n_cst, n_ch) if *n_op == op && n_ch.len() >= ch.len() => {
+                    (*n_cst, n_ch)
+                }
+                _ => continue,
+            };
+            let rest_cst = match cst {
+                Some(c) if n_cst == Some(c) => None,
+                Some(_) => continue,
+                None => n_cst,
+            };
+
+            for (binds, used) in self.match_ch(ch, n_ch, &EBinds::new()) {
+                let rest_ch: Vec<Id> = n_ch
+                    .iter()
+                    .enumerate()
+                    .filter(|(pos, _)| !used.contains(pos))
+                    .map(|(_, &c)| c)
+                    .collect();
+                let rest = if rest_ch.is_empty() && rest_cst.is_none() {
+                    None
+                } else {
+                    Some((op, rest_ch, rest_cst))
+                };
+                res.push(EMatch {
+                    class: id,
+                    binds,
+                    rest,
+                });
+            }
+        }
+
+        res
+    }
+
+    fn ematch(&self, pat: &EPat, id: Id, binds: &EBinds) -> Vec<EBinds> {
+        let id = self.find(id);
+        match pat {
+            EPat::Var(name) => match binds.get(name) {
+                Some(&b_id) if self.find(b_id) == id => vec![binds.clone()],
+                Some(_) => vec![],
+                None => {
+                    let mut binds = binds.clone();
+                    binds.insert(name.clone(), id);
+                    vec![binds]
+                }
+            },
+            EPat::Op(op, cst, ch) => {
+                let mut res = Vec::new();
+                for node in self.classes[id].iter() {
+                    if let ENode::Op(n_op, n_cst, n_ch) = node {
+                        if n_op == op && n_cst == cst && n_ch.len() == ch.len() {
+                            res.extend(self.match_ch(ch, n_ch, binds).into_iter().map(|(b, _)| b));
+                        }
+                    }
+                }
+                res
+            }
+        }
+    }
+
+    // All ways of matching each pattern with a distinct target, given with
+    // the positions of the targets used
+    fn match_ch(&self, pats: &[EPat], targets: &[Id], binds: &EBinds) -> Vec<(EBinds, Vec<usize>)> {
+        let mut sols = vec![(binds.clone(), Vec::new())];
+        for pat in pats {
+            let mut next_sols = Vec::new();
+            for (binds, used) in sols {
+                for t in (0..targets.len()).filter(|t| !used.contains(t)) {
+                    for new_binds in self.ematch(pat, targets[t], &binds) {
+                        let mut new_used = used.clone();
+                        new_used.push(t);
+                        next_sols.push((new_binds, new_used));
+                    }
+                }
+            }
+            sols = next_sols;
+        }
+
+        sols
+    }
+
+    fn apply_match(&mut self, rhs: &EPat, m: &EMatch) -> Result<bool> {
+        let new_id = match &m.rest {
+            None => self.instantiate(rhs, &m.binds)?,
+            Some((op, rest_ch, rest_cst)) => {
+                let (mut ch, mut cst) = (rest_ch.clone(), *rest_cst);
+                // an rhs of the same op is flattened in the node
+                match rhs {
+                    EPat::Op(r_op, r_cst, r_ch) if r_op == op => {
+                        for pat in r_ch {
+                            ch.push(self.instantiate(pat, &m.binds)?);
+                        }
+                        if let Some(c) = r_cst {
+                            let width = self.width;
+                            cst = Some(cst.map_or(*c, |old| compute_op(*op, old, *c, width)));
+                        }
+                    }
+                    _ => ch.push(self.instantiate(rhs, &m.binds)?),
+                }
+                self.add(ENode::Op(*op, cst, ch))
+            }
+        };
+
+        Ok(self.union(m.class, new_id))
+    }
+
+    fn instantiate(&mut self, pat: &EPat, binds: &EBinds) -> Result<Id> {
+        match pat {
+            EPat::Var(name) => binds
+                .get(name)
+                .map(|&id| self.find(id))
+                .ok_or_else(|| Error::UnboundPatternVar(name.clone())),
+            EPat::Op(op, cst, ch) => {
+                let ch = ch
+                    .iter()
+                    .map(|pat| self.instantiate(pat, binds))
+                    .collect::<Result<Vec<Id>>>()?;
+                Ok(self.add(ENode::Op(*op, *cst, ch)))
+            }
+        }
+    }
+
+    // The cheapest term of each class holding a sum is given to the linear
+    // MBA solver, its result is added to the class. A term is solved once.
+    fn solve_linear_mba(&mut self, solved: &mut HashSet<String>) -> Result<()> {
+        // terms are all built before the e-graph changes
+        let best = self.best_nodes(node_cost);
+        let mut terms = Vec::new();
+        for id in self.class_ids() {
+            if self.classes[id]
+                .iter()
+                .any(|n| matches!(n, ENode::Op('+', _, _)))
+            {
+                let arena = self.build_arena(id, &best)?;
+                if solved.insert(arena.print()?) {
+                    terms.push((id, arena));
+                }
+            }
+        }
+
+        for (id, mut arena) in terms {
+            let root = arena.root_node;
+            if reduce::simplify(&mut arena, root)? > 0 {
+                let new_id = self.add_arena(&arena, root)?;
+                self.union(id, new_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Cheapest node of each class with its total cost, the cost of a term
+    // being the sum of the costs of its nodes
+    fn best_nodes<F>(&self, cost: F) -> Vec<Option<(usize, ENode)>>
+    where
+        F: Fn(&ENode) -> usize,
+    {
+        let mut best: Vec<Option<(usize, ENode)>> = vec![None; self.classes.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for id in self.class_ids() {
+                for node in self.classes[id].iter() {
+                    let node_cost = match node {
+                        ENode::Var(_) => Some(cost(node)),
+                        ENode::Op(_, _, ch) => ch.iter().try_fold(cost(node), |acc, &c| {
+                            best[self.find(c)]
+                                .as_ref()
+                                .map(|(c_cost, _)| acc.saturating_add(*c_cost))
+                        }),
+                    };
+
+                    if let Some(node_cost) = node_cost {
+                        if best[id].as_ref().is_none_or(|(b, _)| node_cost < *b) {
+                            best[id] = Some((node_cost, node.clone()));
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        best
+    }
+
+    // Cheapest term of the class of id, according to cost, in a new arena
+    pub fn extract<F>(&self, id: Id, cost: F) -> Result<Arena>
+    where
+        F: Fn(&ENode) -> usize,
+    {
+        let best = self.best_nodes(cost);
+        self.build_arena(id, &best)
+    }
+
+    fn build_arena(&self, id: Id, best: &[Option<(usize, ENode)>]) -> Result<Arena> {
+        let mut arena = Arena::with_width(self.width)?;
+        arena.root_node = self.insert_best(&mut arena, id, String::new(), best)?;
+        Ok(arena)
+    }
+
+    // sign holds the unary ops met above id, from the outermost
+    fn insert_best(
+        &self,
+        arena: &mut Arena,
+        id: Id,
+        mut sign: String,
+        best: &[Option<(usize, ENode)>],
+    ) -> Result<usize> {
+        let id = self.find(id);
+        let (_, node) = best[id].as_ref().ok_or(Error::NoTerm(id))?;
+        match node {
+            ENode::Var(name) => {
+                let val = arena.intern(name);
+                let sign = cancel_sign(&sign);
+                Ok(arena.leaf(VarTerm { val, sign })?)
+            }
+            ENode::Op(s @ ('-' | '~'), _, ch) => {
+                sign.push(*s);
+                self.insert_best(arena, ch[0], sign, best)
+            }
+            ENode::Op(op, cst, ch) => {
+                let (mut terms, mut cst) = (Vec::new(), *cst);
+                self.flat_terms(*op, ch, best, &mut terms, &mut cst);
+                let ch_idx = terms
+                    .into_iter()
+                    .rev()
+                    .map(|c| self.insert_best(arena, c, String::new(), best))
+                    .collect::<Result<Vec<usize>>>()?;
+                Ok(arena.node_with(cancel_sign(&sign), *op, cst, &ch_idx)?)
+            }
+        }
+    }
+
+    // Children of a node of op, those whose cheapest node is of the same op
+    // being replaced by their own children, as in the arena
+    fn flat_terms(
+        &self,
+        op: char,
+        ch: &[Id],
+        best: &[Option<(usize, ENode)>],
+        terms: &mut Vec<Id>,
+        cst: &mut Option<u64>,
+    ) {
+        for &c in ch {
+            match &best[self.find(c)] {
+                Some((_, ENode::Op(c_op, c_cst, c_ch))) if *c_op == op => {
+                    if let Some(c_cst) = c_cst {
+                        let width = self.width;
+                        *cst = Some(cst.map_or(*c_cst, |old| compute_op(op, old, *c_cst, width)));
+                    }
+                    self.flat_terms(op, c_ch, best, terms, cst);
+                }
+                _ => terms.push(c),
+            }
+        }
+    }
+}
+
+// Simplify the expression at idx by equality saturation, its cheapest
+// equivalent term (in num of arena elements) is returned in a new arena
+pub fn optimize(
+    arena: &Arena,
+    idx: usize,
+    rules: &[Rule],
+    cfg: &SaturationConfig,
+) -> Result<(Arena, SaturationReport)> {
+    let mut egraph = EGraph::new(arena.width());
+    let root = egraph.add_arena(arena, idx)?;
+    let report = egraph.saturate(rules, cfg)?;
+
+    Ok((egraph.extract(root, node_cost)?, report))
+}
@@ -0,0 +1,201 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::error::ArenaError;
+use crate::expr::arena::Arena;
+use crate::expr::utils::width_mask;
+
+#[derive(Debug, PartialEq)]
+pub enum EquivResult {
+    Exhaustive(),
+    Probable(usize),
+    Counterexample(BTreeMap<String, u64>),
+}
+
+impl fmt::Display for EquivResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EquivResult::Exhaustive() => write!(f, "equivalent (exhaustive)"),
+            EquivResult::Probable(n) => write!(f, "probably equivalent ({} samples)", n),
+            EquivResult::Counterexample(vals) => {
+                let vals_str: Vec<String> = vals
+                    .iter()
+                    .map(|(v, c)| format!("{}={:#x}", v, c))
+                    .collect();
+                write!(f, "counterexample: {}", vals_str.join(", "))
+            }
+        }
+    }
+}
+
+pub struct EquivConfig {
+    // all inputs are checked if width * num of vars is at most this
+    pub max_exhaustive_bits: u32,
+    // num of random inputs, on top of the corner values
+    pub num_samples: usize,
+    pub seed: u64,
+}
+
+impl Default for EquivConfig {
+    fn default() -> Self {
+        EquivConfig {
+            max_exhaustive_bits: 24,
+            num_samples: 10000,
+            seed: 0x5eed,
+        }
+    }
+}
+
+// SplitMix64, enough for sampling inputs
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform enough in 0..n, n > 0
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+const BATCH_LEN: usize = 4096;
+
+pub fn check_arenas(a1: &Arena, a2: &Arena) -> Result<EquivResult, ArenaError> {
+    check(a1, a1.root_node, a2, a2.root_node, &EquivConfig::default())
+}
+
+// Compare expression idx1 of a1 with expression idx2 of a2, on all inputs
+// if there are few enough, else on corner values and random samples.
+pub fn check(
+    a1: &Arena,
+    idx1: usize,
+    a2: &Arena,
+    idx2: usize,
+    cfg: &EquivConfig,
+) -> Result<EquivResult, ArenaError> {
+    if a1.width() != a2.width() {
+        return Err(ArenaError::WrongWidth());
+    }
+    let width = a1.width();
+    let mask = width_mask(width);
+
+    // vars are matched by name, their ids differ between arenas
+    let mut vars: BTreeSet<&str> = BTreeSet::new();
+    for (arena, idx) in [(a1, idx1), (a2, idx2)] {
+        for v in arena.gather_vars(idx)? {
+            vars.insert(arena.var_name(v)?);
+        }
+    }
+    let vars: Vec<&str> = vars.into_iter().collect();
+
+    let mut checker = Checker {
+        a1,
+        idx1,
+        a2,
+        idx2,
+        vars: &vars,
+        inputs: Vec::with_capacity(BATCH_LEN),
+        num_checked: 0,
+    };
+
+    let num_bits = width as u32 * vars.len() as u32;
+    if num_bits <= cfg.max_exhaustive_bits.min(63) {
+        for i in 0..(1u64 << num_bits) {
+            let input = (0..vars.len())
+                .map(|pos| (i >> (pos as u32 * width as u32)) & mask)
+                .collect();
+            if let Some(res) = checker.push(input)? {
+                return Ok(res);
+            }
+        }
+        return Ok(checker.flush()?.unwrap_or(EquivResult::Exhaustive()));
+    }
+
+    // on narrow widths, some of them wrap around
+    let msb = 1 << (width - 1);
+    let corners = [0, 1, 2, mask, mask - 1, msb, msb - 1, msb + 1].map(|c| c & mask);
+    let mut rng = Rng::new(cfg.seed);
+
+    // each var on each corner value, the others on a random corner
+    for pos in 0..vars.len() {
+        for &c in corners.iter() {
+            let input = (0..vars.len())
+                .map(|p| {
+                    if p == pos {
+                        c
+                    } else {
+                        corners[rng.below(corners.len() as u64) as usize]
+                    }
+                })
+                .collect();
+            if let Some(res) = checker.push(input)? {
+                return Ok(res);
+            }
+        }
+    }
+
+    for _ in 0..cfg.num_samples {
+        let input = (0..vars.len()).map(|_| rng.next_u64() & mask).collect();
+        if let Some(res) = checker.push(input)? {
+            return Ok(res);
+        }
+    }
+
+    Ok(checker
+        .flush()?
+        .unwrap_or(EquivResult::Probable(checker.num_checked)))
+}
+
+// Inputs are evaluated by batches on both expressions
+struct Checker<'a> {
+    a1: &'a Arena,
+    idx1: usize,
+    a2: &'a Arena,
+    idx2: usize,
+    vars: &'a [&'a str],
+    inputs: Vec<Vec<u64>>,
+    num_checked: usize,
+}
+
+impl Checker<'_> {
+    fn push(&mut self, input: Vec<u64>) -> Result<Option<EquivResult>, ArenaError> {
+        self.inputs.push(input);
+        if self.inputs.len() < BATCH_LEN {
+            return Ok(None);
+        }
+        self.flush()
+    }
+
+    fn flush(&mut self) -> Result<Option<EquivResult>, ArenaError> {
+        let res1 = self.a1.eval_batch(self.idx1, self.vars, &self.inputs)?;
+        let res2 = self.a2.eval_batch(self.idx2, self.vars, &self.inputs)?;
+        self.num_checked += self.inputs.len();
+
+        let res = res1
+            .iter()
+            .zip(res2.iter())
+            .position(|(v1, v2)| v1 != v2)
+            .map(|i| {
+                EquivResult::Counterexample(
+                    self.vars
+                        .iter()
+                        .map(|v| v.to_string())
+                        .zip(self.inputs[i].iter().cloned())
+                        .collect(),
+                )
+            });
+        self.inputs.clear();
+
+        Ok(res)
+    }
+}
@@ -1,36 +1,229 @@
+use std::error;
+use std::fmt;
+
+// Byte offsets of a part of the parsed line, end excluded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+}
+
+// The String of WrongSeqChar is the hint, the one of a literal error its text
 #[derive(Debug, PartialEq)]
 pub enum ParseError {
-    MissClosePar(String),
-    MissOpenPar(String),
-    WrongSeqChar(String),
-    WrongChar(String),
-    DanglingNegSign(),
-    NotOp(),
+    MissClosePar(Span),
+    MissOpenPar(Span),
+    WrongSeqChar(String, Span),
+    WrongChar(char, Span),
+    DanglingNegSign(Span),
+    NotOp(Span),
+    InvalidLiteral(String, Span),
+    LiteralOutOfRange(String, Span),
 }
 
-#[derive(Debug, PartialEq)]
-pub enum DAGError {
-    RPNEmpty(),
-    RPNSyntaxError(),
-    SingleTerm(),
-    VarError(),
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::MissClosePar(span)
+            | ParseError::MissOpenPar(span)
+            | ParseError::WrongSeqChar(_, span)
+            | ParseError::WrongChar(_, span)
+            | ParseError::DanglingNegSign(span)
+            | ParseError::NotOp(span)
+            | ParseError::InvalidLiteral(_, span)
+            | ParseError::LiteralOutOfRange(_, span) => *span,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            ParseError::MissClosePar(_) => "missing closing parenthesis",
+            ParseError::MissOpenPar(_) => "missing opening parenthesis",
+            ParseError::WrongSeqChar(..) => "unexpected token",
+            ParseError::WrongChar(..) => "unexpected character",
+            ParseError::DanglingNegSign(_) => "dangling sign",
+            ParseError::NotOp(_) => "not an operator",
+            ParseError::InvalidLiteral(..) => "invalid literal",
+            ParseError::LiteralOutOfRange(..) => "literal out of range",
+        }
+    }
+
+    pub fn hint(&self) -> String {
+        match self {
+            ParseError::MissClosePar(_) => "this `(` is never closed".to_string(),
+            ParseError::MissOpenPar(_) => "this `)` has no matching `(`".to_string(),
+            ParseError::WrongSeqChar(hint, _) => hint.clone(),
+            ParseError::WrongChar(c, _) => format!("`{}` is not part of the syntax", c),
+            ParseError::DanglingNegSign(_) => "this sign applies to nothing".to_string(),
+            ParseError::NotOp(_) => "expected an operator".to_string(),
+            ParseError::InvalidLiteral(text, _) => format!("`{}` is not an integer", text),
+            ParseError::LiteralOutOfRange(text, _) => format!("`{}` does not fit in 64 bits", text),
+        }
+    }
+
+    // Offending line with a caret under the span, the way rustc does:
+    //
+    // error: unexpected token
+    //  --> 1:4
+    //   |
+    // 1 | x+(^y)
+    //   |    ^ binary operator `^` follows `(`
+    pub fn render(&self, line: &str) -> String {
+        let span = self.span();
+        let start = span.start.min(line.len());
+        let end = span.end.clamp(start, line.len());
+        let col = line[..start].chars().count();
+        let len = line[start..end].chars().count().max(1);
+
+        format!(
+            "error: {}\n --> 1:{}\n  |\n1 | {}\n  | {}{} {}\n",
+            self.title(),
+            col + 1,
+            line,
+            " ".repeat(col),
+            "^".repeat(len),
+            self.hint()
+        )
+    }
 }
 
-#[derive(Debug, PartialEq)]
-pub enum ExprError {
-    RPNEmpty(),
-    RPNSyntaxError(),
-    SingleTerm(),
-    VarError(),
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let span = self.span();
+        write!(
+            f,
+            "{} at {}..{}: {}",
+            self.title(),
+            span.start,
+            span.end,
+            self.hint()
+        )
+    }
 }
 
+impl error::Error for ParseError {}
+
 #[derive(Debug, PartialEq)]
 pub enum ArenaError {
     NotANode(),
     ParentIsLeaf(),
     ElemIsLeaf(),
     ElemIsNode(),
+    WrongWidth(),
+    LiteralOutOfRange { value: u64, width: u8 },
+    MissingVar(String),
+    EmptyNode(),
+    NoElem(usize),
+    StaleElem(usize),
+    FreeElem(),
+    UnknownOp(char),
+    UnknownSign(String),
+    UnknownVar(usize),
+    WrongInputLen(usize),
+}
+
+impl fmt::Display for ArenaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArenaError::NotANode() => write!(f, "element is not a node"),
+            ArenaError::ParentIsLeaf() => write!(f, "a leaf can't have children"),
+            ArenaError::ElemIsLeaf() => write!(f, "element is a leaf"),
+            ArenaError::ElemIsNode() => write!(f, "element is a node"),
+            ArenaError::WrongWidth() => {
+                write!(f, "width should be in 1..=64 and match the operands")
+            }
+            ArenaError::LiteralOutOfRange { value, width } => {
+                write!(f, "literal {} does not fit in {} bits", value, width)
+            }
+            ArenaError::MissingVar(name) => write!(f, "no value for variable `{}`", name),
+            ArenaError::EmptyNode() => write!(f, "node has no term"),
+            ArenaError::NoElem(idx) => write!(f, "no element at index {}", idx),
+            ArenaError::StaleElem(idx) => write!(f, "stale handle on index {}", idx),
+            ArenaError::FreeElem() => write!(f, "element has been freed"),
+            ArenaError::UnknownOp(op) => write!(f, "unknown operator `{}`", op),
+            ArenaError::UnknownSign(sign) => write!(f, "unknown sign `{}`", sign),
+            ArenaError::UnknownVar(id) => write!(f, "no variable of id {}", id),
+            ArenaError::WrongInputLen(len) => {
+                write!(f, "input of len {} does not match the variables", len)
+            }
+        }
+    }
+}
+
+impl error::Error for ArenaError {}
+
+// Error of the crate entry points, parse and arena errors are wrapped
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Parse(ParseError),
+    Arena(ArenaError),
+    RPNEmpty(),
+    RPNSyntaxError(),
+    VarError(),
+    TooManyVars(usize),
+    NotPowerOfTwo(usize),
+    EmptyPn(),
+    SignInBitwise(String),
+    UnboundPatternVar(String),
+    NoTerm(usize),
+    CstInBitwise(u64),
+    NoSignature(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Arena(e) => write!(f, "{}", e),
+            Error::RPNEmpty() => write!(f, "empty expression"),
+            Error::RPNSyntaxError() => write!(f, "malformed rpn"),
+            Error::VarError() => write!(f, "invalid variable name"),
+            Error::TooManyVars(n) => write!(f, "{} variables, at most 16 are supported", n),
+            Error::NotPowerOfTwo(n) => write!(f, "length {} is not a power of 2", n),
+            Error::EmptyPn() => write!(f, "empty polish notation"),
+            Error::SignInBitwise(sign) => write!(f, "sign `{}` in bitwise formula", sign),
+            Error::UnboundPatternVar(name) => {
+                write!(
+                    f,
+                    "pattern variable `{}` is not bound by the left side",
+                    name
+                )
+            }
+            Error::NoTerm(id) => write!(f, "e-class {} has no finite term", id),
+            Error::CstInBitwise(cst) => write!(f, "constant {} has no truth table", cst),
+            Error::NoSignature(idx) => {
+                write!(f, "element {} has no input or more than 16", idx)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Parse(e) => Some(e),
+            Error::Arena(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<ArenaError> for Error {
+    fn from(e: ArenaError) -> Self {
+        Error::Arena(e)
+    }
 }
 
-//pub type Result<T> = std::result::Result<T, ParseError>;
-//pub type Result<T> = std::result::Result<T, DAGError>;
+pub type Result<T> = std::result::Result<T, Error>;
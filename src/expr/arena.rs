@@ -1,42 +1,130 @@
-use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::collections::{BTreeSet, HashMap};
 use std::mem;
 
-use super::expr::{Bitwise, Expr};
+use super::kind::{Expr, Other};
 use super::node::{Leaf, Node, VarTerm};
+use super::symbol::SymbolTable;
 use super::utils::{
-    compute_op, compute_sign, fn_node, match_elem, match_elem_mut, node_is_bitwise,
+    compute_sign, is_poly_mba, match_elem, match_elem_mut, node_is_bitwise, node_is_mba_term,
+    node_is_product, width_mask, DEFAULT_WIDTH,
 };
 
 use crate::error::ArenaError;
 
+#[derive(Clone)]
 pub struct Arena {
     pub root_node: usize,
+    width: u8,
+    symbols: SymbolTable,
     elems: Vec<Elem>,
     free_slots: Vec<usize>,
+    gens: Vec<u32>,
+    keys: HashMap<ElemKey, usize>,
 }
 
+// Handle on an element, checked against the generation of its slot: once
+// the element is freed or the arena compacted, the handle is stale
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ElemId {
+    idx: usize,
+    gen: u32,
+}
+
+impl ElemId {
+    pub fn idx(&self) -> usize {
+        self.idx
+    }
+}
+
+// Element designated either by a raw index, or by a checked handle
+pub trait ElemRef {
+    fn slot(&self, arena: &Arena) -> Result<usize, ArenaError>;
+}
+
+impl ElemRef for usize {
+    fn slot(&self, _arena: &Arena) -> Result<usize, ArenaError> {
+        Ok(*self)
+    }
+}
+
+impl ElemRef for ElemId {
+    fn slot(&self, arena: &Arena) -> Result<usize, ArenaError> {
+        match (arena.gens.get(self.idx), arena.elems.get(self.idx)) {
+            (Some(&gen), Some(e)) if gen == self.gen && !matches!(e, Elem::Free) => Ok(self.idx),
+            _ => Err(ArenaError::StaleElem(self.idx)),
+        }
+    }
+}
+
+// Structure of an element: op, sign, cst and children of a node, variable
+// and sign of a leaf. Each registered structure is stored once.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ElemKey {
+    Node(char, String, Option<u64>, Vec<usize>),
+    Leaf(usize, String),
+}
+
+// Polish notation element, children of a sub-node are closed by Close
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PnElem {
+    Op(char),
+    Var(usize),
+    Cst(u64),
+    Close,
+}
+
+#[derive(Clone, Default)]
 pub enum Elem {
     Node(Node),
     Leaf(Leaf),
+    #[default]
     Free,
 }
 
-impl Default for Elem {
-    fn default() -> Self {
-        Elem::Free
+impl Elem {
+    fn pars(&self) -> &[usize] {
+        match self {
+            Elem::Node(n) => &n.pars,
+            Elem::Leaf(l) => &l.pars,
+            Elem::Free => &[],
+        }
     }
-}
 
-impl Elem {
-    fn get_par_id(&self) -> Option<usize> {
-        match_elem(self, |n| n.par, |l| l.par)
+    fn pars_mut(&mut self) -> Option<&mut Vec<usize>> {
+        match self {
+            Elem::Node(n) => Some(&mut n.pars),
+            Elem::Leaf(l) => Some(&mut l.pars),
+            Elem::Free => None,
+        }
     }
 
-    fn graph_label_str(&self) -> String {
-        match_elem(self, |n| n.graph_label_str(), |l| l.graph_label_str())
+    fn key(&self) -> Option<ElemKey> {
+        match self {
+            Elem::Node(n) => Some(ElemKey::Node(n.op(), n.sign(), n.cst, n.ch())),
+            Elem::Leaf(l) => Some(ElemKey::Leaf(l.val.val, l.val.sign.clone())),
+            Elem::Free => None,
+        }
     }
+
+    fn graph_label_str(&self, symbols: &SymbolTable) -> String {
+        match self {
+            Elem::Node(n) => n.graph_label_str(),
+            Elem::Leaf(l) => l.graph_label_str(symbols),
+            Elem::Free => String::new(),
+        }
+    }
+
     fn graph_edge_str(&self) -> Option<String> {
-        match_elem(self, |n| Some(n.graph_edge_str()), |_| None)
+        match self {
+            Elem::Node(n) => Some(n.graph_edge_str()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -44,59 +132,241 @@ impl Arena {
     pub fn new() -> Self {
         Arena {
             root_node: 0,
+            width: DEFAULT_WIDTH,
+            symbols: SymbolTable::new(),
             elems: Vec::new(),
             free_slots: Vec::new(),
+            gens: Vec::new(),
+            keys: HashMap::new(),
         }
     }
 
-    pub fn node(&mut self, sign: String, op: char) -> usize {
-        // TODO: check if node already registered
-        let idx = self.len();
-        let node = Node::new(idx, sign, op);
-        self.elems.push(Elem::Node(node));
-        idx
+    // Arena whose constants are computed modulo 2^width
+    pub fn with_width(width: u8) -> Result<Self, ArenaError> {
+        if width == 0 || width > 64 {
+            return Err(ArenaError::WrongWidth());
+        }
+
+        Ok(Arena {
+            width,
+            ..Arena::new()
+        })
     }
 
-    pub fn leaf(&mut self, val: VarTerm) -> usize {
-        // TODO: check if leaf already registered
-        let idx = self.len();
-        let leaf = Leaf {
-            idx,
-            val,
-            par: None,
+    pub fn width(&self) -> u8 {
+        self.width
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    // id of the variable name, registered if new
+    pub fn intern(&mut self, name: &str) -> usize {
+        self.symbols.intern(name)
+    }
+
+    pub fn var_name(&self, id: usize) -> Result<&str, ArenaError> {
+        self.symbols.name(id).ok_or(ArenaError::UnknownVar(id))
+    }
+
+    // Empty node, to be filled with push_ch and push_cst. It is not shared:
+    // use node_with to get a hash-consed node.
+    pub fn node(&mut self, sign: String, op: char) -> Result<usize, ArenaError> {
+        if !"+.^|&".contains(op) {
+            return Err(ArenaError::UnknownOp(op));
+        }
+        check_sign(&sign)?;
+
+        Ok(self.alloc(|idx| Elem::Node(Node::new(idx, sign, op))))
+    }
+
+    // Node with all its terms, the existing one is returned if an identical
+    // node was already registered. Children csts are not folded, the node
+    // is stored as given.
+    pub fn node_with(
+        &mut self,
+        sign: String,
+        op: char,
+        cst: Option<u64>,
+        ch_idx: &[usize],
+    ) -> Result<usize, ArenaError> {
+        for &idx in ch_idx {
+            self.get(idx)?;
+        }
+
+        let cst = cst.map(|c| c & width_mask(self.width));
+        let key = ElemKey::Node(op, sign.clone(), cst, ch_idx.to_vec());
+        if let Some(&idx) = self.keys.get(&key) {
+            return Ok(idx);
+        }
+
+        let idx = self.node(sign, op)?;
+        let mut temp_n = mem::take(&mut self.elems[idx]);
+        let res = match &mut temp_n {
+            Elem::Node(n) => {
+                n.cst = cst;
+                self.attach_ch(n, ch_idx)
+            }
+            _ => Err(ArenaError::NotANode()),
         };
-        self.elems.push(Elem::Leaf(leaf));
-        idx
+        self.elems[idx] = temp_n;
+        res?;
+
+        self.keys.insert(key, idx);
+        Ok(idx)
     }
 
-    fn len(&self) -> usize {
-        self.elems.len()
+    // Leaves are always shared: the same signed variable is stored once
+    pub fn leaf(&mut self, val: VarTerm) -> Result<usize, ArenaError> {
+        if val.val >= self.symbols.len() {
+            return Err(ArenaError::UnknownVar(val.val));
+        }
+        check_sign(&val.sign)?;
+
+        let key = ElemKey::Leaf(val.val, val.sign.clone());
+        if let Some(&idx) = self.keys.get(&key) {
+            return Ok(idx);
+        }
+
+        let idx = self.alloc(|idx| {
+            Elem::Leaf(Leaf {
+                idx,
+                val,
+                pars: Vec::new(),
+            })
+        });
+        self.keys.insert(key, idx);
+        Ok(idx)
+    }
+
+    // Freed slots are reused before the arena grows
+    fn alloc<F>(&mut self, make: F) -> usize
+    where
+        F: FnOnce(usize) -> Elem,
+    {
+        match self.free_slots.pop() {
+            Some(idx) => {
+                self.elems[idx] = make(idx);
+                idx
+            }
+            None => {
+                let idx = self.elems.len();
+                self.elems.push(make(idx));
+                self.gens.push(0);
+                idx
+            }
+        }
     }
 
-    pub fn get(&self, idx: usize) -> &Elem {
-        self.elems.get(idx).expect("elem not found at index!")
+    // Renumber the live elements from 0, in their current order, so that
+    // no freed slot is left. The table gives the new index of each old one,
+    // None for freed slots.
+    pub fn compact(&mut self) -> Vec<Option<usize>> {
+        let mut remap = vec![None; self.elems.len()];
+        let mut num_live = 0;
+        for (idx, e) in self.elems.iter().enumerate() {
+            if !matches!(e, Elem::Free) {
+                remap[idx] = Some(num_live);
+                num_live += 1;
+            }
+        }
+        let new_idx = |idx: usize| remap[idx].unwrap_or(idx);
+
+        let elems = mem::take(&mut self.elems);
+        for mut e in elems {
+            match &mut e {
+                Elem::Node(n) => {
+                    n.idx = new_idx(n.idx);
+                    n.expr.map_ch(new_idx);
+                }
+                Elem::Leaf(l) => l.idx = new_idx(l.idx),
+                Elem::Free => continue,
+            }
+            if let Some(pars) = e.pars_mut() {
+                pars.iter_mut().for_each(|p| *p = new_idx(*p));
+            }
+            self.elems.push(e);
+        }
+
+        self.keys = mem::take(&mut self.keys)
+            .into_iter()
+            .map(|(key, idx)| {
+                let key = match key {
+                    ElemKey::Node(op, sign, cst, ch) => {
+                        ElemKey::Node(op, sign, cst, ch.into_iter().map(new_idx).collect())
+                    }
+                    key => key,
+                };
+                (key, new_idx(idx))
+            })
+            .collect();
+        self.root_node = new_idx(self.root_node);
+        self.free_slots.clear();
+
+        // handles from before are all stale
+        let gen = self.gens.iter().max().map_or(0, |g| g.wrapping_add(1));
+        self.gens = vec![gen; self.elems.len()];
+
+        remap
+    }
+
+    // Checked handle on the element at idx
+    pub fn elem_id(&self, idx: usize) -> Result<ElemId, ArenaError> {
+        self.get(idx)?;
+        Ok(ElemId {
+            idx,
+            gen: self.gens[idx],
+        })
+    }
+
+    // Number of live elements, freed slots excluded
+    pub fn num_elems(&self) -> usize {
+        self.elems
+            .iter()
+            .filter(|e| !matches!(e, Elem::Free))
+            .count()
+    }
+
+    // Parents of the element at idx, one entry per use as a child
+    pub fn get_pars(&self, idx: impl ElemRef) -> Result<Vec<usize>, ArenaError> {
+        Ok(self.get(idx)?.pars().to_vec())
+    }
+
+    // Freed slots are no elements either
+    pub fn get(&self, idx: impl ElemRef) -> Result<&Elem, ArenaError> {
+        let idx = idx.slot(self)?;
+        match self.elems.get(idx) {
+            Some(Elem::Free) | None => Err(ArenaError::NoElem(idx)),
+            Some(e) => Ok(e),
+        }
     }
 
-    pub fn get_node(&self, idx: usize) -> Result<&Node, ArenaError> {
-        let e = self.get(idx);
+    fn get_mut(&mut self, idx: usize) -> Result<&mut Elem, ArenaError> {
+        match self.elems.get_mut(idx) {
+            Some(Elem::Free) | None => Err(ArenaError::NoElem(idx)),
+            Some(e) => Ok(e),
+        }
+    }
 
-        match e {
+    pub fn get_node(&self, idx: impl ElemRef) -> Result<&Node, ArenaError> {
+        match self.get(idx)? {
             Elem::Node(n) => Ok(n),
             _ => Err(ArenaError::NotANode()),
         }
     }
 
-    pub fn get_ch(&self, idx: usize) -> Vec<usize> {
-        match_elem(self.get(idx), |n| n.ch(), |_| vec![])
+    pub fn get_ch(&self, idx: impl ElemRef) -> Result<Vec<usize>, ArenaError> {
+        match_elem(self.get(idx)?, |n| n.ch(), |_| vec![])
     }
 
-    pub fn get_ch_len(&self, idx: usize) -> usize {
-        match_elem(self.get(idx), |n| n.ch().len(), |_| 0)
+    pub fn get_ch_len(&self, idx: impl ElemRef) -> Result<usize, ArenaError> {
+        match_elem(self.get(idx)?, |n| n.ch().len(), |_| 0)
     }
 
-    pub fn get_num_terms(&self, idx: usize) -> usize {
+    pub fn get_num_terms(&self, idx: impl ElemRef) -> Result<usize, ArenaError> {
         match_elem(
-            self.get(idx),
+            self.get(idx)?,
             |n| {
                 if n.cst.is_none() {
                     n.ch().len()
@@ -108,53 +378,178 @@ impl Arena {
         )
     }
 
-    pub(super) fn copy_op_sign(&self, idx: usize) -> Option<(char, String)> {
-        match_elem(self.get(idx), |n| Some((n.op(), n.sign())), |_| None)
+    pub fn copy_op_sign(&self, idx: impl ElemRef) -> Result<(char, String), ArenaError> {
+        let n = self.get_node(idx)?;
+        Ok((n.op(), n.sign()))
     }
 
-    fn take_cst(&mut self, idx: usize) -> Option<u32> {
-        match_elem_mut(self.get_mut(idx), &mut |n| n.cst.take(), &mut |_| None)
+    pub fn get_sign(&self, idx: impl ElemRef) -> Result<String, ArenaError> {
+        match_elem(self.get(idx)?, |n| n.sign(), |l| l.val.sign.clone())
     }
 
-    fn take_ch(&mut self, idx: usize) -> Option<Vec<usize>> {
-        match_elem_mut(
-            self.get_mut(idx),
-            &mut |n| Some(n.expr.take_ch()),
-            &mut |_| None,
-        )
+    pub fn get_cst(&self, idx: impl ElemRef) -> Result<Option<u64>, ArenaError> {
+        match_elem(self.get(idx)?, |n| n.cst, |_| None)
+    }
+
+    fn take_cst(&mut self, idx: usize) -> Result<Option<u64>, ArenaError> {
+        self.unregister(idx);
+        match_elem_mut(self.get_mut(idx)?, &mut |n| n.cst.take(), &mut |_| None)
     }
 
-    fn get_mut(&mut self, idx: usize) -> &mut Elem {
-        self.elems.get_mut(idx).expect("elem not found at index!")
+    // The element at idx is about to change, its structure is no longer
+    // the registered one
+    fn unregister(&mut self, idx: usize) {
+        if let Some(key) = self.elems.get(idx).and_then(Elem::key) {
+            if self.keys.get(&key) == Some(&idx) {
+                self.keys.remove(&key);
+            }
+        }
     }
 
     fn remove_elem(&mut self, idx: usize) -> Result<Elem, ArenaError> {
-        let rem_elem = mem::replace(self.get_mut(idx), Elem::Free);
+        self.get(idx)?;
+        self.unregister(idx);
+        let rem_elem = mem::replace(&mut self.elems[idx], Elem::Free);
+        self.gens[idx] = self.gens[idx].wrapping_add(1);
         self.free_slots.push(idx);
         Ok(rem_elem)
     }
 
-    pub fn get_preorder(&self, idx: usize, only_node: bool) -> Vec<usize> {
+    // Remove one use of idx by par_idx
+    fn unlink(&mut self, idx: usize, par_idx: usize) -> Result<(), ArenaError> {
+        if let Some(pars) = self.get_mut(idx)?.pars_mut() {
+            if let Some(pos) = pars.iter().position(|&p| p == par_idx) {
+                pars.swap_remove(pos);
+            }
+        }
+        Ok(())
+    }
+
+    // Free the subtree rooted at idx, if it is not used by another node.
+    // Shared descendants are kept as long as another node uses them.
+    pub fn remove_subtree(&mut self, idx: impl ElemRef) -> Result<(), ArenaError> {
+        let idx = idx.slot(self)?;
+        if !self.get(idx)?.pars().is_empty() {
+            return Ok(());
+        }
+
+        let mut idx_stack = vec![idx];
+        while let Some(aux_idx) = idx_stack.pop() {
+            let elem = self.remove_elem(aux_idx)?;
+            for ch_idx in match_elem(&elem, |n| n.ch(), |_| vec![])? {
+                self.unlink(ch_idx, aux_idx)?;
+                if self.get(ch_idx)?.pars().is_empty() {
+                    idx_stack.push(ch_idx);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Put the subtree rooted at idx_from in slot idx_to, the parents of
+    // idx_to are left untouched and its previous children are freed if not
+    // used elsewhere. A shared idx_from is copied, else it is moved.
+    pub fn replace(
+        &mut self,
+        idx_to: impl ElemRef,
+        idx_from: impl ElemRef,
+    ) -> Result<(), ArenaError> {
+        let (idx_to, idx_from) = (idx_to.slot(self)?, idx_from.slot(self)?);
+        let pars = self.get(idx_to)?.pars().to_vec();
+        let shared = !self.get(idx_from)?.pars().is_empty() || idx_from == self.root_node;
+        if idx_to == idx_from {
+            return Ok(());
+        }
+
+        let mut elem = if shared {
+            self.get(idx_from)?.clone()
+        } else {
+            self.remove_elem(idx_from)?
+        };
+        match_elem_mut(
+            &mut elem,
+            &mut |n| {
+                n.idx = idx_to;
+                n.pars = pars.clone();
+            },
+            &mut |l| {
+                l.idx = idx_to;
+                l.pars = pars.clone();
+            },
+        )?;
+
+        for ch_idx in match_elem(&elem, |n| n.ch(), |_| vec![])? {
+            if !shared {
+                self.unlink(ch_idx, idx_from)?;
+            }
+            self.push_par(ch_idx, idx_to)?;
+        }
+
+        // the previous children of idx_to lose a parent
+        self.unregister(idx_to);
+        let old_elem = mem::replace(&mut self.elems[idx_to], elem);
+        for ch_idx in match_elem(&old_elem, |n| n.ch(), |_| vec![])? {
+            self.unlink(ch_idx, idx_to)?;
+            self.remove_subtree(ch_idx)?;
+        }
+
+        if let Some(key) = self.elems[idx_to].key() {
+            self.keys.entry(key).or_insert(idx_to);
+        }
+
+        Ok(())
+    }
+
+    // Kind of the node computed again from its children, as when it was
+    // built: a rewritten child may turn an Other parent into an MBA.
+    // Returns whether the kind changed.
+    pub fn reclassify(&mut self, idx: impl ElemRef) -> Result<bool, ArenaError> {
+        let idx = idx.slot(self)?;
+        let (op, ch) = match self.get(idx)? {
+            Elem::Node(n) => (n.expr.op(), n.expr.ch()),
+            Elem::Leaf(_) => return Ok(false),
+            Elem::Free => return Err(ArenaError::FreeElem()),
+        };
+        let mut expr = Expr::Other(Other::new(op));
+        self.push_ch_expr(&mut expr, &ch);
+
+        match self.get_mut(idx)? {
+            Elem::Node(n) => {
+                let changed = mem::discriminant(&n.expr) != mem::discriminant(&expr);
+                n.expr = expr;
+                Ok(changed)
+            }
+            _ => Err(ArenaError::NotANode()),
+        }
+    }
+
+    pub fn get_preorder(
+        &self,
+        idx: impl ElemRef,
+        only_node: bool,
+    ) -> Result<Vec<usize>, ArenaError> {
+        let idx = idx.slot(self)?;
         let mut idx_stack = vec![idx];
         let mut res: Vec<usize> = Vec::new();
         while let Some(idx) = idx_stack.pop() {
-            if only_node & self.is_leaf(idx) {
+            let e = self.get(idx)?;
+            if only_node & self.is_leaf(idx)? {
                 continue;
             }
 
             res.push(idx);
-            let e = self.get(idx);
-            match_elem(e, |n| idx_stack.append(&mut n.ch().clone()), |_| ())
+            match_elem(e, |n| idx_stack.append(&mut n.ch().clone()), |_| ())?;
         }
 
-        res
+        Ok(res)
     }
 
     pub fn graph_str(&self) -> String {
         let graph_label_str = self
             .elems
             .iter()
-            .map(Elem::graph_label_str)
+            .map(|e| e.graph_label_str(&self.symbols))
             .collect::<Vec<String>>()
             .join("");
         let graph_edge_str = self
@@ -166,221 +561,234 @@ impl Arena {
         "digraph {\n".to_string() + &graph_label_str + &graph_edge_str + "}"
     }
 
-    pub fn print(&self) -> String {
+    pub fn print(&self) -> Result<String, ArenaError> {
         self.elem_str(self.root_node)
     }
 
-    pub fn elem_str(&self, idx: usize) -> String {
-        let mut str_stack: VecDeque<String> = VecDeque::new();
-        let mut suff_stack: Vec<String> = Vec::new();
-        let mut par_idx: Vec<usize> = Vec::new();
+    pub fn elem_str(&self, idx: impl ElemRef) -> Result<String, ArenaError> {
+        let idx = idx.slot(self)?;
+        // suffixes of the open nodes, with their number of children left
+        let mut open: Vec<(String, usize)> = Vec::new();
         let mut res = String::new();
 
-        let mut pre_order = self.get_preorder(idx, false);
-        pre_order.reverse();
-
-        while let Some(idx) = pre_order.pop() {
-            let e = self.get(idx);
-
-            // Not elegant
-            if let Some(p_id) = e.get_par_id() {
-                while let Some(&aux_id) = par_idx.last() {
-                    // if current elem's parent id is different current parent,
-                    // then parsing of all current parent children is done
-                    // -> push parent suffix, change current parent
-                    if p_id != aux_id {
-                        res.push_str(&suff_stack.pop().expect("should have found suffix"));
-                        par_idx.pop();
-                    } else {
-                        break;
-                    }
+        let mut idx_stack = vec![idx];
+        while let Some(idx) = idx_stack.pop() {
+            // all children of the innermost nodes have been printed
+            while let Some((_, 0)) = open.last() {
+                if let Some((s_str, _)) = open.pop() {
+                    res.push_str(&s_str);
                 }
             }
+            if let Some((_, left)) = open.last_mut() {
+                *left -= 1;
+            }
 
-            match_elem(e, |_| par_idx.push(idx), |_| ());
+            let e = self.get(idx)?;
             let (p_str, s_str) = match_elem(
                 e,
-                |n| n.pref_suff(),
-                |l| (format!("{:?}", l.val), "".to_string()),
-            );
+                |n| Ok(n.pref_suff()),
+                |l| {
+                    // multi-char names are braced, "+{rax}{rbx}/" stays readable
+                    let name = self.var_name(l.val.val)?;
+                    let l_str = if name.chars().count() > 1 {
+                        format!("{}{{{}}}", l.val.sign, name)
+                    } else {
+                        format!("{}{}", l.val.sign, name)
+                    };
+                    Ok((l_str, "".to_string()))
+                },
+            )??;
             res.push_str(&p_str);
-            if s_str.len() > 0 {
-                suff_stack.push(s_str);
+
+            let mut ch = match_elem(e, |n| n.ch(), |_| vec![])?;
+            if !s_str.is_empty() {
+                open.push((s_str, ch.len()));
             }
+            idx_stack.append(&mut ch);
         }
 
-        res.push_str(&suff_stack.join(""));
-
-        while let Some(r_str) = str_stack.pop_front() {
-            res.push_str(&r_str);
+        // the last suffixes are output from the outermost one
+        for (s_str, _) in open {
+            res.push_str(&s_str);
         }
 
-        res
+        Ok(res)
     }
 
-    fn set_par(&mut self, idx: usize, par_id: usize) {
+    fn push_par(&mut self, idx: usize, par_id: usize) -> Result<(), ArenaError> {
         match_elem_mut(
-            self.get_mut(idx),
-            &mut |n: &mut Node| n.par = Some(par_id),
-            &mut |l: &mut Leaf| l.par = Some(par_id),
+            self.get_mut(idx)?,
+            &mut |n: &mut Node| n.pars.push(par_id),
+            &mut |l: &mut Leaf| l.pars.push(par_id),
         )
     }
 
     // pre-cond: children are supposed to have had their cst taken if
     // compatible with expr
-    fn push_ch_expr(&self, expr: &mut Expr, ch_idx: &Vec<usize>) {
+    fn push_ch_expr(&self, expr: &mut Expr, ch_idx: &[usize]) {
         if let Some(new_expr) = expr.push_ch_mut(self, ch_idx) {
-            mem::replace(expr, new_expr);
+            *expr = new_expr;
         }
     }
 
-    fn push_ch_node(&mut self, node: &mut Node, ch_idx: &Vec<usize>) {
+    fn push_ch_node(&mut self, node: &mut Node, ch_idx: &[usize]) -> Result<(), ArenaError> {
         // take all compatible cst from future ch
         // regarding sign and op associativity
         // and compute them when current node's cst
-        //node.cst = self.filter_cst(&mut node.expr, node.cst, &ch_idx);
-        node.cst = {
-            let op = node.expr.op();
-            ch_idx
-                .iter()
-                .filter_map(|&idx| {
-                    if let Some((ch_op, ch_sign)) = self.copy_op_sign(idx) {
-                        if op == ch_op {
-                            if let Some(ch_cst) = self.take_cst(idx) {
-                                return Some(compute_sign(ch_cst, ch_sign));
-                            }
-                        }
+        // a shared child keeps its cst, its other parents need it
+        let width = self.width;
+        let op = node.expr.op();
+        for &idx in ch_idx {
+            if let Ok((ch_op, ch_sign)) = self.copy_op_sign(idx) {
+                if op == ch_op && self.get(idx)?.pars().is_empty() {
+                    if let Some(ch_cst) = self.take_cst(idx)? {
+                        node.push_cst(compute_sign(ch_cst, &ch_sign, width)?, width);
                     }
-                    None
-                })
-                .fold(node.cst, |acc, cst| {
-                    if let Some(acc_v) = acc {
-                        Some(compute_op(node.expr.op(), acc_v, cst))
-                    } else {
-                        Some(cst)
-                    }
-                })
-        };
+                }
+            }
+        }
 
         // all adequate ch's have been taken
-        // push all ch to new parent
-        self.push_ch_expr(&mut node.expr, &ch_idx);
+        self.attach_ch(node, ch_idx)
+    }
+
+    // push all ch to new parent, and set it as their parent
+    fn attach_ch(&mut self, node: &mut Node, ch_idx: &[usize]) -> Result<(), ArenaError> {
+        self.push_ch_expr(&mut node.expr, ch_idx);
 
-        // set new parent to all new children
         for idx in ch_idx {
-            self.set_par(*idx, node.idx);
+            self.push_par(*idx, node.idx)?;
         }
+
+        Ok(())
     }
 
-    pub(super) fn push_ch(
-        &mut self,
-        idx: usize,
-        idx_ch_vec: &Vec<usize>,
-    ) -> Result<(), ArenaError> {
-        let mut temp_n = mem::take(self.get_mut(idx));
-        match_elem_mut(
-            &mut temp_n,
-            &mut |n| {
-                self.push_ch_node(n, &idx_ch_vec);
-                Ok(())
-            },
-            &mut |_| Err(ArenaError::ParentIsLeaf()),
-        )?;
+    pub fn push_ch(&mut self, idx: impl ElemRef, idx_ch_vec: &[usize]) -> Result<(), ArenaError> {
+        let idx = idx.slot(self)?;
+        for &ch_idx in idx_ch_vec {
+            self.get(ch_idx)?;
+        }
 
+        self.unregister(idx);
+        let mut temp_n = mem::take(self.get_mut(idx)?);
+        let res = match &mut temp_n {
+            Elem::Node(n) => self.push_ch_node(n, idx_ch_vec),
+            _ => Err(ArenaError::ParentIsLeaf()),
+        };
         self.elems[idx] = temp_n;
 
-        Ok(())
+        res
     }
 
-    pub(super) fn push_cst(&mut self, idx: usize, cst: u32) -> Result<(), ArenaError> {
+    pub fn push_cst(&mut self, idx: impl ElemRef, cst: u64) -> Result<(), ArenaError> {
+        let idx = idx.slot(self)?;
+        let width = self.width;
+        self.unregister(idx);
         match_elem_mut(
-            &mut self.get_mut(idx),
+            self.get_mut(idx)?,
             &mut |n| {
-                n.push_cst(cst);
+                n.push_cst(cst, width);
                 Ok(())
             },
             &mut |_| Err(ArenaError::ParentIsLeaf()),
-        )?;
-
-        Ok(())
+        )?
     }
-    pub(super) fn move_ch(&mut self, idx_to: usize, idx_from: usize) -> Result<(), ArenaError> {
-        let from_ch: Vec<usize>;
-
-        if let Some(ch) = self.take_ch(idx_from) {
-            from_ch = ch;
-        } else {
-            return Err(ArenaError::NotANode());
-        }
 
-        self.push_ch(idx_to, &from_ch)?;
-
-        // update children's new parent id
-        for ch_idx in from_ch {
-            match_elem_mut(
-                self.get_mut(ch_idx),
-                &mut |n| n.par = Some(idx_to),
-                &mut |l| l.par = Some(idx_to),
-            );
-        }
+    fn is_leaf(&self, idx: usize) -> Result<bool, ArenaError> {
+        match_elem(self.get(idx)?, |_| false, |_| true)
+    }
 
-        self.remove_elem(idx_from)?;
+    pub fn is_bitwise(&self, idx: impl ElemRef) -> Result<bool, ArenaError> {
+        match_elem(self.get(idx)?, |n| node_is_bitwise(&n.expr), |_| true)
+    }
 
-        Ok(())
+    pub fn is_mba(&self, idx: impl ElemRef) -> Result<bool, ArenaError> {
+        match_elem(
+            self.get(idx)?,
+            |n| matches!(n.expr, Expr::Mba(_)),
+            |_| false,
+        )
     }
 
-    fn is_leaf(&self, idx: usize) -> bool {
-        match_elem(self.get(idx), |_| false, |_| true)
+    pub fn is_mba_term(&self, idx: impl ElemRef) -> Result<bool, ArenaError> {
+        match_elem(
+            self.get(idx)?,
+            |n| node_is_mba_term(&n.expr, n.cst),
+            |_| false,
+        )
     }
 
-    pub fn is_bitwise(&self, idx: usize) -> bool {
-        match_elem(self.get(idx), |n| node_is_bitwise(&n.expr, n.cst), |_| true)
+    pub fn is_product(&self, idx: impl ElemRef) -> Result<bool, ArenaError> {
+        match_elem(self.get(idx)?, |n| node_is_product(&n.expr), |_| false)
     }
 
-    pub fn is_mba(&self, idx: usize) -> bool {
-        match_elem(self.get(idx), |n| matches!(n.expr, Expr::MBA(_)), |_| false)
+    // Not classified when built, the sum is checked from its children
+    pub fn is_poly_mba(&self, idx: impl ElemRef) -> Result<bool, ArenaError> {
+        match_elem(
+            self.get(idx)?,
+            |n| is_poly_mba(self, n.expr.op(), &n.expr.ch()),
+            |_| false,
+        )
     }
 
-    pub fn gather_vars(&self, idx: usize) -> BTreeSet<char> {
+    pub fn gather_vars(&self, idx: impl ElemRef) -> Result<BTreeSet<usize>, ArenaError> {
+        let idx = idx.slot(self)?;
         let mut res = BTreeSet::new();
         let mut idx_vec = vec![idx];
 
         while let Some(aux_idx) = idx_vec.pop() {
             match_elem(
-                self.get(aux_idx),
+                self.get(aux_idx)?,
                 |n| {
                     idx_vec.append(&mut n.expr.ch());
                 },
                 |l| {
                     res.insert(l.val.val);
                 },
-            );
+            )?;
         }
 
-        res
+        Ok(res)
     }
 
-    pub fn pn(&self, idx: usize) -> Vec<(char, String)> {
+    pub fn pn(&self, idx: impl ElemRef) -> Result<Vec<(PnElem, String)>, ArenaError> {
+        let idx = idx.slot(self)?;
         let mut res = Vec::new();
         let mut idx_vec = vec![Some(idx)];
 
         while let Some(aux_opt) = idx_vec.pop() {
             if let Some(aux_idx) = aux_opt {
-                res.push(match_elem(
-                    self.get(aux_idx),
+                let (elem, cst) = match_elem(
+                    self.get(aux_idx)?,
                     |n| {
-                        idx_vec.append(&mut n.expr.ch().into_iter().map(Some).collect());
-                        idx_vec.push(None);
-                        (n.expr.op(), n.sign())
+                        // children of a sub-node are closed by Close, the root ones are not
+                        if aux_idx != idx {
+                            idx_vec.push(None);
+                        }
+                        idx_vec.append(&mut n.expr.ch().into_iter().rev().map(Some).collect());
+                        ((PnElem::Op(n.expr.op()), n.sign()), n.cst)
                     },
-                    |l| (l.val.val, l.val.sign.clone()),
-                ));
+                    |l| ((PnElem::Var(l.val.val), l.val.sign.clone()), None),
+                )?;
+                res.push(elem);
+                // the cst of a node is one more of its operands
+                if let Some(cst) = cst {
+                    res.push((PnElem::Cst(cst), String::new()));
+                }
             } else {
-                res.push(('!', "".to_string()));
+                res.push((PnElem::Close, "".to_string()));
             }
         }
 
         res.reverse();
-        res
+        Ok(res)
+    }
+}
+
+// Signs are made of '-' and '~' only
+fn check_sign(sign: &str) -> Result<(), ArenaError> {
+    if sign.chars().any(|c| c != '-' && c != '~') {
+        return Err(ArenaError::UnknownSign(sign.to_string()));
     }
+    Ok(())
 }
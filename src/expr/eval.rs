@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use super::arena::{Arena, ElemRef};
+use super::utils::{compute_op, compute_sign, match_elem};
+
+use crate::error::ArenaError;
+
+// Expression flattened in post-order, to be evaluated on a value stack
+enum Instr {
+    Var(usize, String),
+    Node(char, usize, Option<u64>, String),
+}
+
+struct EvalProg {
+    instrs: Vec<Instr>,
+    width: u8,
+}
+
+impl EvalProg {
+    fn new(arena: &Arena, idx: usize, vars: &[&str]) -> Result<Self, ArenaError> {
+        // reversed pre-order: children come before their parent
+        let instrs = arena
+            .get_preorder(idx, false)?
+            .into_iter()
+            .rev()
+            .map(|aux_idx| {
+                match_elem(
+                    arena.get(aux_idx)?,
+                    |n| {
+                        if n.ch().is_empty() && n.cst.is_none() {
+                            return Err(ArenaError::EmptyNode());
+                        }
+                        Ok(Instr::Node(n.op(), n.ch().len(), n.cst, n.sign()))
+                    },
+                    |l| {
+                        let name = arena.var_name(l.val.val)?;
+                        let pos = vars
+                            .iter()
+                            .position(|&v| v == name)
+                            .ok_or_else(|| ArenaError::MissingVar(name.to_string()))?;
+                        Ok(Instr::Var(pos, l.val.sign.clone()))
+                    },
+                )?
+            })
+            .collect::<Result<Vec<Instr>, ArenaError>>()?;
+
+        Ok(EvalProg {
+            instrs,
+            width: arena.width(),
+        })
+    }
+
+    fn run(&self, input: &[u64], stack: &mut Vec<u64>) -> Result<u64, ArenaError> {
+        stack.clear();
+        for instr in self.instrs.iter() {
+            let val = match instr {
+                Instr::Var(pos, sign) => compute_sign(input[*pos], sign, self.width)?,
+                Instr::Node(op, num_ch, cst, sign) => {
+                    let ch_val = stack
+                        .drain(stack.len() - num_ch..)
+                        .chain(*cst)
+                        .reduce(|acc, v| compute_op(*op, acc, v, self.width))
+                        .ok_or(ArenaError::EmptyNode())?;
+                    compute_sign(ch_val, sign, self.width)?
+                }
+            };
+            stack.push(val);
+        }
+
+        stack.pop().ok_or(ArenaError::EmptyNode())
+    }
+}
+
+impl Arena {
+    // Value of the expression at idx, modulo 2^width
+    pub fn eval(&self, idx: impl ElemRef, vals: &HashMap<String, u64>) -> Result<u64, ArenaError> {
+        let idx = idx.slot(self)?;
+        let vars: Vec<&str> = vals.keys().map(|v| v.as_str()).collect();
+        let input: Vec<u64> = vars.iter().map(|&v| vals[v]).collect();
+        let prog = EvalProg::new(self, idx, &vars)?;
+
+        prog.run(&input, &mut Vec::new())
+    }
+
+    // Value of the expression at idx for each input, input[i] being the
+    // value of vars[i]. The expression is flattened only once.
+    pub fn eval_batch(
+        &self,
+        idx: impl ElemRef,
+        vars: &[&str],
+        inputs: &[Vec<u64>],
+    ) -> Result<Vec<u64>, ArenaError> {
+        let idx = idx.slot(self)?;
+        let prog = EvalProg::new(self, idx, vars)?;
+        if let Some(input) = inputs.iter().find(|input| input.len() != vars.len()) {
+            return Err(ArenaError::WrongInputLen(input.len()));
+        }
+
+        let mut stack = Vec::new();
+
+        inputs
+            .iter()
+            .map(|input| prog.run(input, &mut stack))
+            .collect()
+    }
+}
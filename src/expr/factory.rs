@@ -1,153 +1,136 @@
 use std::collections::VecDeque;
 
-use std::mem;
-
 use super::arena::Arena;
 use super::node::VarTerm;
-use super::utils::compute_sign;
+use super::utils::{compute_op, compute_sign, width_mask, DEFAULT_WIDTH};
+
+use crate::error::{ArenaError, Error, Result};
+use crate::lexer::{is_ident, Token};
 
-use crate::error::ExprError;
+// Expression as read from the rpn, before insertion in the arena.
+// Nested nodes of the same op are merged while reading.
+enum Term {
+    Leaf(String, String),
+    Cst(u64),
+    Node(char, String, Vec<Term>),
+}
 
 pub struct ArenaFactory;
 impl ArenaFactory {
-    pub fn new_arena(rpn: &mut VecDeque<String>) -> Result<Arena, ExprError> {
-        let mut arena = Arena::new();
+    pub fn new_arena(rpn: &mut VecDeque<Token>) -> Result<Arena> {
+        ArenaFactory::new_arena_width(rpn, DEFAULT_WIDTH)
+    }
+
+    pub fn new_arena_width(rpn: &mut VecDeque<Token>, width: u8) -> Result<Arena> {
+        let mut arena = Arena::with_width(width)?;
         arena.root_node = ArenaFactory::build_expr(rpn, &mut arena)?;
 
         Ok(arena)
     }
 
-    fn build_expr(rpn: &mut VecDeque<String>, arena: &mut Arena) -> Result<usize, ExprError> {
-        fn take_node_stack(
-            arena: &mut Arena,
-            curr_node: &mut Option<usize>,
-            node_stack: &mut VecDeque<usize>,
-        ) {
-            // take curr not on node_stack
-            if let Some(idx) = curr_node.take() {
-                node_stack.push_back(idx);
-            }
+    fn build_expr(rpn: &mut VecDeque<Token>, arena: &mut Arena) -> Result<usize> {
+        if rpn.is_empty() {
+            return Err(Error::RPNEmpty());
+        }
 
-            // clean node_stack -> node with enough children are merge/pushed onto parents
-            while let Some(idx) = node_stack.pop_back() {
-                let has_2_t = arena.get_num_terms(idx) >= 2;
-                // ... it has at least 2children, it should be merge/push to top of node_stack
-                if let (true, Some(&mut par_idx)) = (has_2_t, node_stack.back_mut()) {
-                    let (par_op, par_sign) =
-                        arena.copy_op_sign(par_idx).expect("should have found node");
-                    let (e_op, e_sign) = arena.copy_op_sign(idx).expect("should have found node");
-                    // if same op,sign as top of stack -> merge children
-                    if par_op == e_op && par_sign == e_sign {
-                        arena.move_ch(par_idx, idx).expect("ch not moved");
-                    } else {
-                        // ... if not set as child
-                        arena.push_ch(par_idx, &vec![idx]).expect("ch not pushed");
+        let width = arena.width();
+        let mut stack: Vec<Term> = Vec::new();
+        while let Some(token) = rpn.pop_front() {
+            let term = match token {
+                Token::Var(name) => {
+                    if !is_ident(&name) {
+                        return Err(Error::VarError());
                     }
-                } else {
-                    // ... else put back on top
-                    node_stack.push_back(idx);
-                    break;
+                    Term::Leaf(name, String::new())
                 }
-            }
+                Token::Const(cst) => {
+                    if cst > width_mask(width) {
+                        return Err(ArenaError::LiteralOutOfRange { value: cst, width }.into());
+                    }
+                    Term::Cst(cst)
+                }
+                Token::UnaryOp(s @ ('-' | '~')) => {
+                    let term = stack.pop().ok_or(Error::RPNSyntaxError())?;
+                    apply_sign(term, s, width)?
+                }
+                Token::BinOp(op @ ('+' | '.' | '^' | '&' | '|')) => {
+                    let t2 = stack.pop().ok_or(Error::RPNSyntaxError())?;
+                    let t1 = stack.pop().ok_or(Error::RPNSyntaxError())?;
+                    apply_op(op, t1, t2, width)
+                }
+                _ => return Err(Error::RPNSyntaxError()),
+            };
+            stack.push(term);
         }
 
-        println!("{}", format!("rpn: {:?}", rpn));
-
-        if rpn.len() == 0 {
-            return Err(ExprError::RPNEmpty());
+        // a lone constant has no node to hold it
+        match (stack.pop(), stack.is_empty()) {
+            (Some(Term::Cst(_)), _) | (None, _) | (_, false) => Err(Error::RPNSyntaxError()),
+            (Some(term), true) => insert(arena, term),
         }
+    }
+}
 
-        let mut prev_leaf = false;
-        let mut curr_node: Option<usize> = None;
-        let mut sign_stack: Vec<String> = Vec::new();
-        let mut sign = String::new();
-        let mut node_stack: VecDeque<usize> = VecDeque::new();
+// Signs are stacked from the outermost, "~~" and "--" cancel out
+fn apply_sign(term: Term, s: char, width: u8) -> Result<Term> {
+    let push_sign = |sign: String| {
+        if sign.starts_with(s) {
+            sign[1..].to_string()
+        } else {
+            format!("{}{}", s, sign)
+        }
+    };
 
-        while let Some(elem) = rpn.pop_back() {
-            let node_vec: Vec<String> = node_stack.iter().map(|i| (&arena).elem_str(*i)).collect();
-            let node_str = curr_node.as_ref().and_then(|i| Some(arena.elem_str(*i)));
-            println!(
-                "{}",
-                format!(
-                    "{:?} - node_stack: {:?} - curr_node: {:?} - elem: {}",
-                    rpn, node_vec, node_str, elem
-                )
-            );
+    Ok(match term {
+        Term::Cst(cst) => Term::Cst(compute_sign(cst, &s.to_string(), width)?),
+        Term::Leaf(name, sign) => Term::Leaf(name, push_sign(sign)),
+        Term::Node(op, sign, ch) => Term::Node(op, push_sign(sign), ch),
+    })
+}
 
-            match elem.as_str() {
-                "+" | "." | "^" | "&" | "|" => {
-                    prev_leaf = false;
-                    if let Some(new_op) = elem.chars().next() {
-                        if let Some(idx) = curr_node {
-                            let (curr_op, _) =
-                                arena.copy_op_sign(idx).expect("should have found op.");
-                            if curr_op == new_op && sign.len() == 0 {
-                                continue;
-                            }
-                        }
-                        if sign.len() > 0 {
-                            sign_stack.push(mem::take(&mut sign));
-                        }
+fn apply_op(op: char, t1: Term, t2: Term, width: u8) -> Term {
+    if let (Term::Cst(c1), Term::Cst(c2)) = (&t1, &t2) {
+        return Term::Cst(compute_op(op, *c1, *c2, width));
+    }
 
-                        take_node_stack(arena, &mut curr_node, &mut node_stack);
+    let mut ch = Vec::new();
+    for t in [t1, t2] {
+        match t {
+            Term::Node(t_op, sign, mut t_ch) if t_op == op && sign.is_empty() => {
+                ch.append(&mut t_ch)
+            }
+            t => ch.push(t),
+        }
+    }
 
-                        curr_node =
-                            Some(arena.node(sign_stack.pop().unwrap_or("".to_string()), new_op));
-                    } else {
-                        unreachable!()
-                    }
-                }
-                "~" | "-" | "-~" | "~-" => {
-                    if sign.len() > 2 {
-                        return Err(ExprError::RPNSyntaxError());
-                    }
-                    sign.push_str(&elem);
-                }
-                _ => {
-                    if let Ok(c_u) = elem.parse::<u32>() {
-                        let cst = compute_sign(c_u, mem::take(&mut sign));
-                        match curr_node.as_mut() {
-                            Some(idx) => arena
-                                .push_cst(*idx, cst)
-                                .expect("should have been able to push cst"),
-                            None => return Err(ExprError::RPNSyntaxError()),
-                        };
-                    } else if let (true, Some(c_var)) = (elem.len() == 1, elem.chars().next()) {
-                        let val = VarTerm {
-                            val: c_var,
-                            sign: mem::take(&mut sign),
-                        };
-                        let id_l = arena.leaf(val);
-                        match curr_node.as_mut() {
-                            Some(idx) => arena
-                                .push_ch(*idx, &vec![id_l])
-                                .expect("should have been able to push."),
-                            None => {
-                                if rpn.len() == 0 {
-                                    return Ok(id_l);
-                                }
-                                return Err(ExprError::RPNSyntaxError());
-                            }
-                        }
-                    } else {
-                        return Err(ExprError::RPNSyntaxError());
-                    }
+    Term::Node(op, String::new(), ch)
+}
 
-                    if prev_leaf {
-                        take_node_stack(arena, &mut curr_node, &mut node_stack);
-                        curr_node = node_stack.pop_back();
+// Children are pushed in reverse, to be printed in reading order.
+// Identical subterms are inserted once.
+fn insert(arena: &mut Arena, term: Term) -> Result<usize> {
+    match term {
+        Term::Leaf(name, sign) => {
+            let val = VarTerm {
+                val: arena.intern(&name),
+                sign,
+            };
+            Ok(arena.leaf(val)?)
+        }
+        Term::Cst(_) => Err(Error::RPNSyntaxError()),
+        Term::Node(op, sign, ch) => {
+            let width = arena.width();
+            let mut cst = None;
+            let mut ch_idx = Vec::new();
+            for t in ch.into_iter().rev() {
+                match t {
+                    Term::Cst(c) => {
+                        cst = Some(cst.map_or(c, |old| compute_op(op, old, c, width)));
                     }
-
-                    sign = String::new();
-                    prev_leaf = true;
+                    t => ch_idx.push(insert(arena, t)?),
                 }
             }
+            Ok(arena.node_with(sign, op, cst, &ch_idx)?)
         }
-
-        if let Some(idx) = curr_node {
-            return Ok(idx);
-        }
-
-        Ok(node_stack.pop_back().ok_or(ExprError::RPNSyntaxError())?)
     }
 }
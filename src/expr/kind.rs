@@ -0,0 +1,183 @@
+use std::mem;
+
+use super::arena::Arena;
+use super::utils::{is_bitwise, is_mba, is_mba_term, is_product};
+
+#[derive(Clone)]
+pub(super) enum Expr {
+    Mba(Mba),         // -> op: +     - ch vec (mba/bitwise terms)
+    MbaTerm(MbaTerm), // -> op: .     - ch: (cst, bitwise)
+    Product(Product), // -> op: .     - ch: vec (bitwise factors)
+    Bitwise(Bitwise), // -> op: bitw. - ch: vec
+    Other(Other),     // -> op: any   - ch: vec of anything
+}
+
+impl Expr {
+    pub(super) fn op(&self) -> char {
+        match self {
+            Self::Mba(_) => '+',
+            Self::MbaTerm(_) => '.',
+            Self::Product(_) => '.',
+            Self::Bitwise(e) => e.op,
+            Self::Other(e) => e.op,
+        }
+    }
+    pub(super) fn ch(&self) -> Vec<usize> {
+        match self {
+            Self::Mba(e) => e.ch.clone(),
+            Self::MbaTerm(e) => vec![e.bitw],
+            Self::Product(e) => e.ch.clone(),
+            Self::Bitwise(e) => e.ch.clone(),
+            Self::Other(e) => e.ch.clone(),
+        }
+    }
+
+    pub(super) fn push_ch_mut(&mut self, arena: &Arena, ch_idx: &[usize]) -> Option<Expr> {
+        match self {
+            Self::Mba(e) => e.push_ch_mut(arena, ch_idx),
+            Self::MbaTerm(e) => e.push_ch_mut(arena, ch_idx),
+            Self::Product(e) => e.push_ch_mut(arena, ch_idx),
+            Self::Bitwise(e) => e.push_ch_mut(arena, ch_idx),
+            Self::Other(e) => e.push_ch_mut(arena, ch_idx),
+        }
+    }
+
+    // Children are renumbered, the kind of expr is unchanged
+    pub(super) fn map_ch<F>(&mut self, func: F)
+    where
+        F: Fn(usize) -> usize,
+    {
+        match self {
+            Self::Mba(e) => e.ch.iter_mut().for_each(|c| *c = func(*c)),
+            Self::MbaTerm(e) => e.bitw = func(e.bitw),
+            Self::Product(e) => e.ch.iter_mut().for_each(|c| *c = func(*c)),
+            Self::Bitwise(e) => e.ch.iter_mut().for_each(|c| *c = func(*c)),
+            Self::Other(e) => e.ch.iter_mut().for_each(|c| *c = func(*c)),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Mba {
+    ch: Vec<usize>,
+}
+
+impl Mba {
+    fn push_ch_mut(&mut self, arena: &Arena, ch_idx: &[usize]) -> Option<Expr> {
+        self.ch.append(&mut ch_idx.to_vec());
+        if is_mba(arena, '+', &self.ch) {
+            None
+        } else {
+            Some(Expr::Other(Other {
+                ch: mem::take(&mut self.ch),
+                op: '+',
+            }))
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MbaTerm {
+    bitw: usize,
+}
+
+impl MbaTerm {
+    fn push_ch_mut(&mut self, arena: &Arena, ch_idx: &[usize]) -> Option<Expr> {
+        let mut ch = vec![self.bitw];
+        ch.append(&mut ch_idx.to_vec());
+        if is_product(arena, '.', &ch) {
+            Some(Expr::Product(Product { ch }))
+        } else {
+            Some(Expr::Other(Other { ch, op: '.' }))
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Product {
+    ch: Vec<usize>,
+}
+
+impl Product {
+    fn push_ch_mut(&mut self, arena: &Arena, ch_idx: &[usize]) -> Option<Expr> {
+        self.ch.append(&mut ch_idx.to_vec());
+        if is_product(arena, '.', &self.ch) {
+            None
+        } else {
+            Some(Expr::Other(Other {
+                ch: mem::take(&mut self.ch),
+                op: '.',
+            }))
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Bitwise {
+    ch: Vec<usize>,
+    op: char,
+}
+
+impl Bitwise {
+    fn push_ch_mut(&mut self, arena: &Arena, ch_idx: &[usize]) -> Option<Expr> {
+        self.ch.append(&mut ch_idx.to_vec());
+        if is_bitwise(arena, self.op, &self.ch) {
+            None
+        } else {
+            Some(Expr::Other(Other {
+                ch: mem::take(&mut self.ch),
+                op: self.op,
+            }))
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Other {
+    ch: Vec<usize>,
+    op: char,
+}
+
+impl Other {
+    pub(super) fn new(op: char) -> Self {
+        Self { ch: Vec::new(), op }
+    }
+
+    fn push_ch_mut(&mut self, arena: &Arena, ch_idx: &[usize]) -> Option<Expr> {
+        self.ch.append(&mut ch_idx.to_vec());
+        match self.op {
+            '+' => {
+                if is_mba(arena, '+', &self.ch) {
+                    Some(Expr::Mba(Mba {
+                        ch: mem::take(&mut self.ch),
+                    }))
+                } else {
+                    None
+                }
+            }
+            '.' => {
+                if is_mba_term(arena, '.', &self.ch) {
+                    Some(Expr::MbaTerm(MbaTerm {
+                        bitw: mem::take(&mut self.ch[0]),
+                    }))
+                } else if is_product(arena, '.', &self.ch) {
+                    Some(Expr::Product(Product {
+                        ch: mem::take(&mut self.ch),
+                    }))
+                } else {
+                    None
+                }
+            }
+            op => {
+                if is_bitwise(arena, op, &self.ch) {
+                    Some(Expr::Bitwise(Bitwise {
+                        ch: mem::take(&mut self.ch),
+                        op,
+                    }))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
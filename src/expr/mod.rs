@@ -1,5 +1,7 @@
 pub mod arena;
-pub mod expr;
+pub mod eval;
 pub mod factory;
+pub mod kind;
 pub mod node;
+pub mod symbol;
 pub mod utils;
@@ -1,10 +1,13 @@
 use std::fmt::{self, Debug};
 
-use super::expr::{Expr, Other};
-use super::utils::compute_op;
+use super::kind::{Expr, Other};
+use super::symbol::SymbolTable;
+use super::utils::{compute_op, width_mask};
 
+// val is the id of the variable in the arena symbol table
+#[derive(Clone)]
 pub struct VarTerm {
-    pub val: char,
+    pub val: usize,
     pub sign: String,
 }
 
@@ -14,12 +17,15 @@ impl Debug for VarTerm {
     }
 }
 
+// pars holds one entry per use of the node as a child, shared subterms
+// having several parents
+#[derive(Clone)]
 pub struct Node {
     pub(super) idx: usize,
     sign: String,
     pub(super) expr: Expr,
-    pub(super) cst: Option<u32>,
-    pub(super) par: Option<usize>,
+    pub(super) cst: Option<u64>,
+    pub(super) pars: Vec<usize>,
 }
 
 impl Node {
@@ -29,15 +35,15 @@ impl Node {
             sign,
             expr: Expr::Other(Other::new(op)),
             cst: None,
-            par: None,
+            pars: Vec::new(),
         }
     }
 
-    pub(super) fn push_cst(&mut self, cst: u32) {
+    pub(super) fn push_cst(&mut self, cst: u64, width: u8) {
         if let Some(old_cst) = self.cst.take() {
-            self.cst = Some(compute_op(self.expr.op(), old_cst, cst));
+            self.cst = Some(compute_op(self.expr.op(), old_cst, cst, width));
         } else {
-            self.cst = Some(cst);
+            self.cst = Some(cst & width_mask(width));
         }
     }
 
@@ -60,7 +66,7 @@ impl Node {
             .map(|c| format!("{} -> {}\n", self.idx, c))
             .collect::<Vec<String>>()
             .join("");
-        if let Some(_) = self.cst {
+        if self.cst.is_some() {
             let cst_arrow = format!("{} -> \"{}c\"\n", self.idx, self.idx);
             format!("{}{}", main_str, cst_arrow)
         } else {
@@ -81,16 +87,16 @@ impl Node {
     }
 
     pub fn has_cst(&self) -> bool {
-        !self.cst.is_none()
+        self.cst.is_some()
     }
 
     pub(super) fn pref_suff(&self) -> (String, String) {
         let (mut p_str, mut s_str) = (String::new(), String::new());
-        let closed_par = self.sign.len() > 0;
-        if self.sign.len() > 0 {
+        let closed_par = !self.sign.is_empty();
+        if !self.sign.is_empty() {
             p_str = format!("{}({}{}", &self.sign, &self.op(), p_str);
         } else {
-            p_str = format!("{}{}", self.op().to_string(), p_str);
+            p_str = format!("{}{}", self.op(), p_str);
         }
 
         if let Some(cst) = self.cst {
@@ -106,17 +112,20 @@ impl Node {
     }
 }
 
+#[derive(Clone)]
 pub struct Leaf {
     pub idx: usize,
     pub val: VarTerm,
-    pub(super) par: Option<usize>,
+    pub(super) pars: Vec<usize>,
 }
 
 impl Leaf {
-    pub(super) fn graph_label_str(&self) -> String {
+    pub(super) fn graph_label_str(&self, symbols: &SymbolTable) -> String {
         format!(
             "{} [label=<{}{:?}>]\n",
-            self.idx, self.val.sign, self.val.val
+            self.idx,
+            self.val.sign,
+            symbols.name(self.val.val).unwrap_or_default()
         )
     }
 }
@@ -1,63 +1,94 @@
 use super::arena::{Arena, Elem};
-use super::expr::Expr;
+use super::kind::Expr;
 use super::node::{Leaf, Node};
 
-pub(super) fn compute_op(op: char, cst1: u32, cst2: u32) -> u32 {
-    match op {
-        '+' => cst1 + cst2,
-        '.' => cst1 * cst2,
+use crate::error::ArenaError;
+
+pub const DEFAULT_WIDTH: u8 = 32;
+
+// Mask of the values representable on width bits
+pub fn width_mask(width: u8) -> u64 {
+    if width >= 64 {
+        !0
+    } else {
+        (1 << width) - 1
+    }
+}
+
+// Value on width bits, sign extended to i64
+pub fn to_signed(cst: u64, width: u8) -> i64 {
+    let shift = 64 - width as u32;
+    ((cst << shift) as i64) >> shift
+}
+
+// All computations are done modulo 2^width
+pub(crate) fn compute_op(op: char, cst1: u64, cst2: u64, width: u8) -> u64 {
+    let res = match op {
+        '+' => cst1.wrapping_add(cst2),
+        '.' => cst1.wrapping_mul(cst2),
         '^' => cst1 ^ cst2,
         '|' => cst1 | cst2,
         '&' => cst1 & cst2,
         _ => unreachable!(),
-    }
+    };
+    res & width_mask(width)
 }
 
-pub fn compute_sign(cst: u32, sign: String) -> u32 {
-    match sign.as_str() {
-        "" => cst,
-        "-" => (-(cst as i32)) as u32,
-        "~" => !cst,
-        "~-" => !((-(cst as i32)) as u32),
-        "-~" => (-((!cst) as i32)) as u32,
-        _ => unreachable!(),
-    }
+// Signs are applied from the innermost (last) one: "-~" is -(~cst)
+pub(crate) fn compute_sign(cst: u64, sign: &str, width: u8) -> Result<u64, ArenaError> {
+    let res = sign.chars().rev().try_fold(cst, |acc, s| match s {
+        '-' => Ok(acc.wrapping_neg()),
+        '~' => Ok(!acc),
+        _ => Err(ArenaError::UnknownSign(sign.to_string())),
+    })?;
+    Ok(res & width_mask(width))
 }
 
-pub(super) fn is_mba(arena: &Arena, op: char, ch: &Vec<usize>) -> bool {
+pub(super) fn is_mba(arena: &Arena, op: char, ch: &[usize]) -> bool {
     if op != '+' {
         return false;
     }
     ch.iter().all(|&ch| {
-        match_elem(
-            arena.get(ch),
-            |n| node_is_bitwise(&n.expr, n.cst) || node_is_mba_term(&n.expr, n.cst),
-            |_| true,
-        )
+        arena
+            .get(ch)
+            .and_then(|e| {
+                match_elem(
+                    e,
+                    |n| node_is_bitwise(&n.expr) || node_is_mba_term(&n.expr, n.cst),
+                    |_| true,
+                )
+            })
+            .unwrap_or(false)
     })
 }
 
-pub(super) fn node_is_mba_term(expr: &Expr, cst: Option<u32>) -> bool {
-    matches!(expr, Expr::MBATerm(_)) && !cst.is_none()
+pub(super) fn node_is_mba_term(expr: &Expr, cst: Option<u64>) -> bool {
+    matches!(expr, Expr::MbaTerm(_)) && cst.is_some()
 }
 
-pub(super) fn node_is_bitwise(expr: &Expr, cst: Option<u32>) -> bool {
-    matches!(expr, Expr::Bitwise(_)) && cst.is_none()
+// A cst is one more operand of the bitwise node: x ^ 0x5c
+pub(super) fn node_is_bitwise(expr: &Expr) -> bool {
+    matches!(expr, Expr::Bitwise(_))
 }
 
-fn ch_bitwise(arena: &Arena, ch: &Vec<usize>) -> bool {
-    ch.iter()
-        .all(|&ch| match_elem(arena.get(ch), |n| node_is_bitwise(&n.expr, n.cst), |_| true))
+fn ch_bitwise(arena: &Arena, ch: &[usize]) -> bool {
+    // missing children are not bitwise
+    ch.iter().all(|&ch| {
+        arena
+            .get(ch)
+            .and_then(|e| match_elem(e, |n| node_is_bitwise(&n.expr), |_| true))
+            .unwrap_or(false)
+    })
 }
 
-pub(super) fn is_bitwise(arena: &Arena, op: char, ch: &Vec<usize>) -> bool {
+pub(super) fn is_bitwise(arena: &Arena, op: char, ch: &[usize]) -> bool {
     if !"^&|".contains(op) {
         return false;
     }
     ch_bitwise(arena, ch)
 }
 
-pub(super) fn is_mba_term(arena: &Arena, op: char, ch: &Vec<usize>) -> bool {
+pub(super) fn is_mba_term(arena: &Arena, op: char, ch: &[usize]) -> bool {
     if op != '.' {
         return false;
     }
@@ -69,58 +100,73 @@ pub(super) fn is_mba_term(arena: &Arena, op: char, ch: &Vec<usize>) -> bool {
     ch_bitwise(arena, ch)
 }
 
-fn fn_elem<F, R>(elem: &Elem, func: F) -> R
-where
-    F: Fn(&Elem) -> R,
-{
-    match elem {
-        Elem::Free => panic!("idx -> free elem"),
-        e => func(e),
+// Product of at least two bitwise factors, the cst being its coefficient
+pub(super) fn is_product(arena: &Arena, op: char, ch: &[usize]) -> bool {
+    if op != '.' {
+        return false;
     }
-}
 
-pub fn fn_node<F, R>(elem: &Elem, func: F) -> R
-where
-    F: FnOnce(&Node) -> R,
-{
-    match elem {
-        Elem::Node(n) => func(n),
-        Elem::Leaf(_) => panic!("idx -> leaf elem"),
-        Elem::Free => panic!("idx -> free elem"),
+    if ch.len() < 2 {
+        return false;
     }
+
+    ch_bitwise(arena, ch)
 }
 
-pub fn fn_node_mut<F, R>(elem: &mut Elem, func: &mut F) -> R
-where
-    F: FnMut(&mut Node) -> R,
-{
-    match elem {
-        Elem::Node(n) => func(n),
-        Elem::Leaf(_) => panic!("idx -> leaf elem"),
-        Elem::Free => panic!("idx -> free elem"),
+pub(super) fn node_is_product(expr: &Expr) -> bool {
+    matches!(expr, Expr::Product(_))
+}
+
+// Sum of bitwise terms, mba terms and products, with at least one product
+pub(super) fn is_poly_mba(arena: &Arena, op: char, ch: &[usize]) -> bool {
+    if op != '+' {
+        return false;
     }
+    let mut has_product = false;
+    let all_terms = ch.iter().all(|&ch| {
+        arena
+            .get(ch)
+            .and_then(|e| {
+                match_elem(
+                    e,
+                    |n| {
+                        has_product |= node_is_product(&n.expr);
+                        node_is_bitwise(&n.expr)
+                            || node_is_mba_term(&n.expr, n.cst)
+                            || node_is_product(&n.expr)
+                    },
+                    |_| true,
+                )
+            })
+            .unwrap_or(false)
+    });
+    all_terms && has_product
 }
 
-pub fn match_elem<FNo, Fl, R>(elem: &Elem, func_n: FNo, func_l: Fl) -> R
+pub fn match_elem<FNo, Fl, R>(elem: &Elem, func_n: FNo, func_l: Fl) -> Result<R, ArenaError>
 where
     FNo: FnOnce(&Node) -> R,
     Fl: FnOnce(&Leaf) -> R,
 {
     match elem {
-        Elem::Node(n) => func_n(n),
-        Elem::Leaf(l) => func_l(l),
-        _ => panic!("idx -> free elem"),
+        Elem::Node(n) => Ok(func_n(n)),
+        Elem::Leaf(l) => Ok(func_l(l)),
+        Elem::Free => Err(ArenaError::FreeElem()),
     }
 }
 
-pub(super) fn match_elem_mut<FNo, Fl, R>(elem: &mut Elem, func_n: &mut FNo, func_l: &mut Fl) -> R
+pub(super) fn match_elem_mut<FNo, Fl, R>(
+    elem: &mut Elem,
+    func_n: &mut FNo,
+    func_l: &mut Fl,
+) -> Result<R, ArenaError>
 where
     FNo: FnMut(&mut Node) -> R,
     Fl: FnMut(&mut Leaf) -> R,
 {
     match elem {
-        Elem::Node(n) => func_n(n),
-        Elem::Leaf(l) => func_l(l),
-        _ => panic!("idx -> free elem"),
+        Elem::Node(n) => Ok(func_n(n)),
+        Elem::Leaf(l) => Ok(func_l(l)),
+        Elem::Free => Err(ArenaError::FreeElem()),
     }
 }
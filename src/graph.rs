@@ -1,12 +1,13 @@
 use std::fs;
+use std::io;
 
 use crate::expr::arena::Arena;
 
 pub struct Graph;
 impl Graph {
-    pub fn write_graph(arena: &Arena) {
+    pub fn write_graph(arena: &Arena) -> io::Result<()> {
         let graph_str = arena.graph_str();
 
-        fs::write("expr_graph.dot", graph_str).expect("Unable to write file");
+        fs::write("expr_graph.dot", graph_str)
     }
 }
@@ -0,0 +1,162 @@
+use std::fmt;
+
+use crate::error::{ParseError, Span};
+
+// Element of an expression. The parentheses only appear in the output of
+// the lexer, never in the RPN built from it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Token {
+    Var(String),
+    Const(u64),
+    BinOp(char),
+    UnaryOp(char),
+    LeftPar,
+    RightPar,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Var(name) => write!(f, "{}", name),
+            Token::Const(cst) => write!(f, "{}", cst),
+            Token::BinOp(op) | Token::UnaryOp(op) => write!(f, "{}", op),
+            Token::LeftPar => write!(f, "("),
+            Token::RightPar => write!(f, ")"),
+        }
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Variable name: ASCII letters, digits and '_', not starting with a digit
+pub fn is_ident(s: &str) -> bool {
+    match s.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => s.chars().all(is_ident_char),
+        _ => false,
+    }
+}
+
+// Split the line in tokens, each with its span. A '-' following an operand
+// is the binary minus, else it is a sign. Operands and operators must
+// alternate.
+pub fn lex(line: &str) -> Result<Vec<(Token, Span)>, ParseError> {
+    let mut tokens: Vec<(Token, Span)> = Vec::new();
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+        let token = if is_ident_char(c) {
+            // literals run as names do: 0x5bd1e995U, 12ULL
+            while let Some(&(pos, next_c)) = chars.peek() {
+                if !is_ident_char(next_c) {
+                    break;
+                }
+                end = pos + next_c.len_utf8();
+                chars.next();
+            }
+
+            let text = &line[start..end];
+            if c.is_ascii_digit() {
+                Token::Const(parse_literal(text, Span::new(start, end))?)
+            } else {
+                Token::Var(text.to_string())
+            }
+        } else {
+            let prev = tokens.last().map(|(t, _)| t);
+            match c {
+                '(' => Token::LeftPar,
+                ')' => Token::RightPar,
+                '~' => Token::UnaryOp('~'),
+                '-' if ends_operand(prev) => Token::BinOp('-'),
+                '-' => Token::UnaryOp('-'),
+                '+' | '.' | '^' | '&' | '|' => Token::BinOp(c),
+                _ => return Err(ParseError::WrongChar(c, Span::new(start, end))),
+            }
+        };
+
+        let span = Span::new(start, end);
+        check_seq(tokens.last().map(|(t, _)| t), &token, span)?;
+        tokens.push((token, span));
+    }
+
+    match tokens.last() {
+        Some((Token::UnaryOp(_), span)) => Err(ParseError::DanglingNegSign(*span)),
+        Some((token @ Token::BinOp(_), span)) => Err(ParseError::WrongSeqChar(
+            format!("{} has no right operand", describe(token)),
+            *span,
+        )),
+        _ => Ok(tokens),
+    }
+}
+
+fn ends_operand(token: Option<&Token>) -> bool {
+    matches!(
+        token,
+        Some(Token::Var(_)) | Some(Token::Const(_)) | Some(Token::RightPar)
+    )
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Var(name) => format!("variable `{}`", name),
+        Token::Const(cst) => format!("constant `{}`", cst),
+        Token::BinOp(op) => format!("binary operator `{}`", op),
+        Token::UnaryOp(op) => format!("sign `{}`", op),
+        Token::LeftPar => "`(`".to_string(),
+        Token::RightPar => "`)`".to_string(),
+    }
+}
+
+fn check_seq(prev: Option<&Token>, token: &Token, span: Span) -> Result<(), ParseError> {
+    let wrong = match token {
+        Token::Var(_) | Token::Const(_) | Token::LeftPar | Token::UnaryOp(_) => ends_operand(prev),
+        // "()" is left to the parenthesis matching
+        Token::RightPar => !matches!(prev, None | Some(Token::LeftPar)) && !ends_operand(prev),
+        Token::BinOp(_) => !ends_operand(prev),
+    };
+
+    if wrong {
+        let hint = match prev {
+            Some(prev) => format!("{} follows {}", describe(token), describe(prev)),
+            None => format!("{} has no left operand", describe(token)),
+        };
+        return Err(ParseError::WrongSeqChar(hint, span));
+    }
+
+    Ok(())
+}
+
+// Integer literal as in C: decimal, hex (0x), binary (0b) or octal (leading
+// 0), with an optional U, L, UL, LL or ULL suffix in any case
+fn parse_literal(text: &str, span: Span) -> Result<u64, ParseError> {
+    let invalid = || ParseError::InvalidLiteral(text.to_string(), span);
+    let body = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let suffix = text[body.len()..].to_lowercase();
+    if !["", "u", "l", "ul", "lu", "ll", "ull", "llu"].contains(&suffix.as_str()) {
+        return Err(invalid());
+    }
+
+    let lower = body.to_lowercase();
+    let (digits, radix) = if let Some(d) = lower.strip_prefix("0x") {
+        (d, 16)
+    } else if let Some(d) = lower.strip_prefix("0b") {
+        (d, 2)
+    } else if lower.len() > 1 && lower.starts_with('0') {
+        (&lower[1..], 8)
+    } else {
+        (lower.as_str(), 10)
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(invalid());
+    }
+
+    // only digits are left, an error is an overflow
+    u64::from_str_radix(digits, radix)
+        .map_err(|_| ParseError::LiteralOutOfRange(text.to_string(), span))
+}
@@ -1,5 +1,11 @@
+pub mod egraph;
+pub mod equiv;
 pub mod error;
 pub mod expr;
 pub mod graph;
+pub mod lexer;
+pub mod obfuscate;
 pub mod parser;
 pub mod reduce;
+pub mod rewrite;
+pub mod synth;
@@ -1,32 +1,56 @@
+use std::collections::VecDeque;
 use std::env;
 
+use dobf::error::Result;
 use dobf::expr::factory::ArenaFactory;
 use dobf::graph::Graph;
+use dobf::lexer::Token;
 use dobf::parser::{parse_rpn, read_lines};
+use dobf::reduce::{simplify_fixpoint, MAX_PASSES};
+
+fn run(cl_line: &mut VecDeque<Token>) -> Result<()> {
+    let mut arena = ArenaFactory::new_arena(cl_line)?;
+    println!("{}", arena.print()?);
+    println!("{}", arena.graph_str());
+    if let Err(e) = Graph::write_graph(&arena) {
+        println!("unable to write graph: {}", e);
+    }
+    println!("bitwise: {}", arena.is_bitwise(arena.root_node)?);
+    println!("mba: {}", arena.is_mba(arena.root_node)?);
+    let root = arena.root_node;
+    simplify_fixpoint(&mut arena, root, MAX_PASSES)?;
+    println!("simplified: {}", arena.print()?);
+
+    Ok(())
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let test = 'x';
-    println!("{}", format!("test {:#?}", test));
+    let test = "x";
+    println!("test {:#?}", test);
+
+    let path = match args.get(1) {
+        Some(path) => path,
+        None => {
+            println!("usage: dobf <file>");
+            return;
+        }
+    };
 
     // File hosts must exist in current path before this produces output
-    if let Ok(lines) = read_lines(&args[1]) {
+    if let Ok(lines) = read_lines(path) {
         // Consumes the iterator, returns an (Optional) String
-        for line_res in lines {
-            if let Ok(line) = line_res {
-                match parse_rpn(line) {
-                    Ok(cl_line) => {
-                        println!("cleaned line: {:?}", cl_line);
-                        let arena = ArenaFactory::new_arena(&mut cl_line.clone()).unwrap();
-                        println!("{}", arena.print());
-                        println!("{}", arena.graph_str());
-                        Graph::write_graph(&arena);
-                        println!("bitwise: {}", arena.is_bitwise(arena.root_node));
-                        println!("mba: {}", arena.is_mba(arena.root_node));
+        for line in lines.map_while(std::result::Result::ok) {
+            println!("expr: {}", line);
+            match parse_rpn(line.clone()) {
+                Ok(mut cl_line) => {
+                    println!("cleaned line: {:?}", cl_line);
+                    if let Err(e) = run(&mut cl_line) {
+                        println!("error: {}", e);
                     }
-                    Err(e) => println!("error cleaning {:?}", e),
                 }
+                Err(e) => println!("{}", e.render(&line)),
             }
         }
     }
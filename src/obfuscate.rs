@@ -0,0 +1,322 @@
+use crate::equiv::Rng;
+use crate::error::{ArenaError, Error, Result};
+use crate::expr::arena::{Arena, Elem};
+use crate::expr::utils::{compute_op, to_signed};
+use crate::reduce::{self, mobius_transform, Core, Input};
+use crate::rewrite::with_sign;
+use crate::synth::{lookup3, BoolExpr, Cost};
+
+// Terms are coef * (product of bitwise exprs), a term without factors
+// being a cst
+type Terms = Vec<(i64, Vec<BoolExpr>)>;
+
+pub struct ObfConfig {
+    // random bitwise terms of each zero identity
+    pub num_terms: usize,
+    // variables of a layer are encoded again in the next one, 1 gives a
+    // linear MBA
+    pub num_layers: usize,
+    // a zero identity is multiplied by a random bitwise term
+    pub polynomial: bool,
+    pub seed: u64,
+}
+
+impl Default for ObfConfig {
+    fn default() -> Self {
+        ObfConfig {
+            num_terms: 3,
+            num_layers: 1,
+            polynomial: false,
+            seed: 0x0bf,
+        }
+    }
+}
+
+// Equivalent MBA of the expression at idx, in the input syntax of
+// parse_rpn. Its non-linear subterms (products, sums under bitwise ops...)
+// are opaque inputs, as for simplify_abstract, printed back as they are.
+// Random bitwise terms f_i with random coefs a_i are added, and the
+// expression is written over the conjunction basis minus their
+// contribution: sum(a_i * f_i) + sum(c_S * (&S)) is a random vector of the
+// null space of [signatures of f_i | basis] and the expression's signature.
+pub fn obfuscate(arena: &Arena, idx: usize, cfg: &ObfConfig) -> Result<String> {
+    // fresh variables are added to a copy of the arena, the sign of the
+    // root is applied to its signature
+    let mut arena = arena.clone();
+    let sign = arena.get_sign(idx)?;
+    let root = with_sign(&mut arena, idx, String::new())?;
+
+    let mut fresh: Vec<(usize, Core)> = Vec::new();
+    let abs_idx = reduce::abstract_term(&mut arena, root, "+^|&", "^|&", &mut fresh)?;
+    let mut abs_idx = split_operand_signs(&mut arena, abs_idx)?;
+    // signs such as -x under a bitwise op are not linear: the whole term is
+    // then a single opaque input
+    if reduce::linear_signature(&arena, abs_idx)?.is_none() {
+        fresh.clear();
+        abs_idx = reduce::abstract_term(&mut arena, root, "", "", &mut fresh)?;
+    }
+    let (inputs, mut sig) =
+        reduce::linear_signature(&arena, abs_idx)?.ok_or(Error::NoSignature(idx))?;
+    if inputs.is_empty() {
+        return Err(Error::NoSignature(idx));
+    }
+    let arena = &arena;
+
+    // ~e = -e - 1, the all-ones function standing for -1
+    for s in sign.chars().rev() {
+        for v in sig.iter_mut() {
+            *v = if s == '~' {
+                v.wrapping_neg().wrapping_add(1)
+            } else {
+                v.wrapping_neg()
+            };
+        }
+    }
+
+    let names = inputs
+        .iter()
+        .map(|i| match i {
+            Input::Var(v) => match fresh.iter().find(|(val, _)| val == v) {
+                Some((_, (op, cst, ch))) => core_infix(arena, *op, *cst, ch),
+                None => Ok(arena.var_name(*v)?.to_string()),
+            },
+            Input::Cst(c) => Ok(c.to_string()),
+        })
+        .collect::<Result<_>>()?;
+    let mut obf = Obfuscator {
+        rng: Rng::new(cfg.seed),
+        width: arena.width(),
+        num_terms: cfg.num_terms,
+        polynomial: cfg.polynomial,
+        inputs,
+        names,
+    };
+    obf.encode(&sig, cfg.num_layers.max(1))
+}
+
+struct Obfuscator {
+    rng: Rng,
+    width: u8,
+    num_terms: usize,
+    polynomial: bool,
+    inputs: Vec<Input>,
+    names: Vec<String>,
+}
+
+impl Obfuscator {
+    fn encode(&mut self, sig: &[i64], layer: usize) -> Result<String> {
+        let mut coeffs = sig.to_vec();
+        mobius_transform(&mut coeffs)?;
+        let mut terms = self.random_terms(&mut coeffs)?;
+
+        // h * (zero identity)
+        if self.polynomial {
+            let mut zero = vec![0; coeffs.len()];
+            let zero_terms = self.random_terms(&mut zero)?;
+            let (h, _) = self.random_bool()?;
+            for (c, mut factors) in zero_terms.into_iter().chain(basis_terms(&zero)) {
+                factors.insert(0, h.clone());
+                terms.push((c, factors));
+            }
+        }
+        terms.extend(basis_terms(&coeffs));
+
+        for i in (1..terms.len()).rev() {
+            let j = self.rng.below(i as u64 + 1) as usize;
+            terms.swap(i, j);
+        }
+        self.render(&terms, layer)
+    }
+
+    // num_terms random bitwise functions with random coefs, their
+    // combination over the basis being subtracted from coeffs
+    fn random_terms(&mut self, coeffs: &mut [i64]) -> Result<Terms> {
+        let mut terms = Vec::new();
+        for _ in 0..self.num_terms {
+            let (e, e_coeffs) = self.random_bool()?;
+            // in -4..=4, 0 excluded
+            let a = self.rng.below(8) as i64 - 4;
+            let a = if a >= 0 { a + 1 } else { a };
+            for (c, e_c) in coeffs.iter_mut().zip(e_coeffs) {
+                *c = c.wrapping_sub(a.wrapping_mul(e_c));
+            }
+            terms.push((a, vec![e]));
+        }
+        Ok(terms)
+    }
+
+    // Non-constant function of (at most) 3 distinct inputs drawn at random,
+    // with its coefficients over the basis
+    fn random_bool(&mut self) -> Result<(BoolExpr, Vec<i64>)> {
+        let num_inputs = self.inputs.len();
+        let k = num_inputs.min(3);
+        loop {
+            // 3 vars table of a function of the first k vars
+            let mut f = self.rng.below(1 << (1 << k)) as u8;
+            for exp in k..3 {
+                f |= f << (1 << exp);
+            }
+            if f == 0 || f == 0xff {
+                continue;
+            }
+            let e = match lookup3(f, Cost::Nodes) {
+                Some(e) => e,
+                None => continue,
+            };
+
+            let mut pos: Vec<u8> = (0..num_inputs as u8).collect();
+            for i in 0..k {
+                let j = i + self.rng.below((num_inputs - i) as u64) as usize;
+                pos.swap(i, j);
+            }
+            let e = e.map_vars(&pos[..k]);
+
+            let tt = e.truth_table(num_inputs as u8)?;
+            let mut e_coeffs: Vec<i64> = (0..tt.num_rows()).map(|i| tt.get(i) as i64).collect();
+            mobius_transform(&mut e_coeffs)?;
+            return Ok((e, e_coeffs));
+        }
+    }
+
+    fn render(&mut self, terms: &[(i64, Vec<BoolExpr>)], layer: usize) -> Result<String> {
+        let mut res = String::new();
+        for (c, factors) in terms {
+            let c = to_signed(*c as u64, self.width);
+            if c == 0 {
+                continue;
+            }
+
+            let mut parts = Vec::new();
+            if c.unsigned_abs() != 1 || factors.is_empty() {
+                parts.push(c.unsigned_abs().to_string());
+            }
+            for e in factors {
+                parts.push(self.bool_infix(e, layer)?);
+            }
+            match (res.is_empty(), c < 0) {
+                (true, true) => res.push('-'),
+                (true, false) => (),
+                (false, true) => res.push_str(" - "),
+                (false, false) => res.push_str(" + "),
+            }
+            res.push_str(&parts.join("."));
+        }
+
+        // all terms cancelled out
+        if res.is_empty() {
+            res = format!("{0}-{0}", self.names[0]);
+        }
+        Ok(res)
+    }
+
+    // Fully parenthesized, the variables being encoded again below the
+    // last layer
+    fn bool_infix(&mut self, e: &BoolExpr, layer: usize) -> Result<String> {
+        match e {
+            BoolExpr::Var(pos) => {
+                let pos = *pos as usize;
+                match self.inputs.get(pos) {
+                    Some(Input::Var(_)) if layer > 1 => {
+                        let sig: Vec<i64> = (0..1 << self.inputs.len())
+                            .map(|i| ((i >> pos) & 1) as i64)
+                            .collect();
+                        Ok(format!("({})", self.encode(&sig, layer - 1)?))
+                    }
+                    Some(_) => Ok(self.names[pos].clone()),
+                    None => Err(ArenaError::UnknownVar(pos).into()),
+                }
+            }
+            BoolExpr::Not(e) => Ok(format!("~{}", self.bool_infix(e, layer)?)),
+            BoolExpr::Op(op, e1, e2) => Ok(format!(
+                "({}{}{})",
+                self.bool_infix(e1, layer)?,
+                op,
+                self.bool_infix(e2, layer)?
+            )),
+        }
+    }
+}
+
+// Terms of a combination over the conjunction basis, its all-ones function
+// being the cst -1
+fn basis_terms(coeffs: &[i64]) -> Terms {
+    coeffs
+        .iter()
+        .enumerate()
+        .filter(|(_, &c)| c != 0)
+        .map(|(i, &c)| match BoolExpr::conj(i) {
+            Some(e) => (c, vec![e]),
+            None => (c.wrapping_neg(), Vec::new()),
+        })
+        .collect()
+}
+
+// Copy of the sum at idx, the sign of each operand written as coef * f or
+// coef * ~f (coef being 1 or -1) plus a cst added to the sum: "~-" in
+// ~-(x^y) gives (x^y) - 1
+fn split_operand_signs(arena: &mut Arena, idx: usize) -> Result<usize> {
+    if !matches!(arena.get(idx)?, Elem::Node(_)) || arena.copy_op_sign(idx)?.0 != '+' {
+        return Ok(idx);
+    }
+
+    let (sign, width) = (arena.get_sign(idx)?, arena.width());
+    let (mut cst, ch) = (arena.get_cst(idx)?, arena.get_ch(idx)?);
+    let mut new_ch = Vec::new();
+    for ch_idx in ch.iter() {
+        let (coef, neg, c) = affine_sign(&arena.get_sign(*ch_idx)?);
+        let ch_sign = match (coef, neg) {
+            (1, false) => "",
+            (1, true) => "~",
+            (_, false) => "-",
+            (_, true) => "-~",
+        };
+        new_ch.push(with_sign(arena, *ch_idx, ch_sign.to_string())?);
+        if c != 0 {
+            cst = Some(compute_op('+', cst.unwrap_or(0), c as u64, width));
+        }
+    }
+    if new_ch == ch && cst == arena.get_cst(idx)? {
+        return Ok(idx);
+    }
+
+    Ok(arena.node_with(sign, '+', cst, &new_ch)?)
+}
+
+// Sign applied to f as (coef, negated, cst): its value is coef * f + cst, or
+// coef * ~f + cst if negated. Signs are applied from the innermost one.
+fn affine_sign(sign: &str) -> (i64, bool, i64) {
+    let (mut coef, mut neg, mut cst) = (1i64, false, 0i64);
+    for s in sign.chars().rev() {
+        match s {
+            // f itself is negated while nothing applies on top of it
+            '~' if coef == 1 && cst == 0 => neg = !neg,
+            '~' => (coef, cst) = (-coef, cst.wrapping_neg().wrapping_sub(1)),
+            _ => (coef, cst) = (-coef, cst.wrapping_neg()),
+        }
+    }
+    (coef, neg, cst)
+}
+
+// Term at idx in the input syntax of parse_rpn, nodes parenthesized
+fn infix(arena: &Arena, idx: usize) -> Result<String> {
+    match arena.get(idx)? {
+        Elem::Leaf(l) => Ok(format!("{}{}", l.val.sign, arena.var_name(l.val.val)?)),
+        Elem::Node(_) => {
+            let (op, sign) = arena.copy_op_sign(idx)?;
+            let (cst, ch) = (arena.get_cst(idx)?, arena.get_ch(idx)?);
+            Ok(format!("{}{}", sign, core_infix(arena, op, cst, &ch)?))
+        }
+        Elem::Free => Err(ArenaError::FreeElem().into()),
+    }
+}
+
+fn core_infix(arena: &Arena, op: char, cst: Option<u64>, ch: &[usize]) -> Result<String> {
+    let mut parts = ch
+        .iter()
+        .map(|&ch_idx| infix(arena, ch_idx))
+        .collect::<Result<Vec<String>>>()?;
+    if let Some(cst) = cst {
+        parts.push(cst.to_string());
+    }
+    Ok(format!("({})", parts.join(&op.to_string())))
+}
@@ -1,193 +1,1252 @@
 use std::collections::{BTreeMap, BTreeSet};
-use std::mem;
+use std::ops::{BitAnd, BitOr, BitXor, Not};
 
 use intbits::Bits;
-use itertools::Itertools;
 
-use crate::expr::arena::Arena;
-use crate::expr::utils::{compute_sign, fn_node};
+use crate::error::{ArenaError, Error, Result};
+use crate::expr::arena::{Arena, Elem, ElemRef, PnElem};
+use crate::expr::node::VarTerm;
+use crate::expr::utils::{compute_op, compute_sign, to_signed, width_mask};
+use crate::synth::{self, BoolExpr, Cost};
 
-fn reduce(arena: &mut Arena, root_idx: usize) {
-    let mut node_idx_vec = vec![root_idx];
+// Linear MBA simplification:
+// each operand of an MBA node is written as coef * f, with f a bitwise
+// function of the node's variables. The sum of all (coef, f) is decomposed
+// over the basis of conjunctions of variables (x, y, x&y, ...) and the node
+// is rewritten as this new linear combination, if it is shorter.
+// The all-ones function of the basis is the cst -1 (~0), so that a cst
+// term c is -c times this function: x + ~x = -1.
+//
+// Polynomial MBA (sums of products of bitwise terms) are expanded the same
+// way: each factor is decomposed over the basis and the products are
+// multiplied out, giving a polynomial in the conjunctions with
+// coefficients modulo 2^width.
+pub fn simplify(arena: &mut Arena, root_idx: usize) -> Result<usize> {
+    // shared nodes are met once per use
+    let mut mba_idx_vec = Vec::new();
+    for idx in arena.get_preorder(root_idx, true)? {
+        if is_reducible(arena, idx)? && !mba_idx_vec.contains(&idx) {
+            mba_idx_vec.push(idx);
+        }
+    }
 
-    while let Some(idx) = node_idx_vec.pop() {
-        let mut ch_vec = arena.get_ch(idx);
-        if !arena.is_mba(idx) {
-            node_idx_vec.append(&mut ch_vec);
+    let mut num_simplified = 0;
+    for idx in mba_idx_vec {
+        // nodes under an already simplified one may have been freed
+        if is_reducible(arena, idx) != Ok(true) {
             continue;
         }
+        let simplified = if arena.is_mba(idx)? {
+            simplify_mba(arena, idx)?
+        } else {
+            simplify_poly(arena, idx)?
+        };
+        if simplified {
+            num_simplified += 1;
+        }
+    }
 
-        let p_str = arena.elem_str(idx);
-        print!("mba: {}", p_str);
+    Ok(num_simplified)
+}
 
-        for ch_idx in ch_vec {
-            if arena.is_bitwise(ch_idx) {
-                fn_node(arena.get(idx), |n| {
-                    if n.has_cst() {
-                        panic!("bitwise expr should not have cst value!")
-                    }
-                });
+// Partial reduction of sums with non-bitwise operands: the maximal
+// non-bitwise subterms of a sum are abstracted as fresh variables, the sum
+// is reduced, and the subterms are substituted back. Sums are processed
+// innermost first, so that nested ones are simplified layer by layer:
+// ((a+b)^c) + 2*((a+b)&c) = (t^c) + 2*(t&c) = t+c = a+b+c
+pub fn simplify_abstract(arena: &mut Arena, root_idx: usize) -> Result<usize> {
+    let mut sum_idx_vec = Vec::new();
+    for idx in arena.get_preorder(root_idx, true)?.into_iter().rev() {
+        if arena.copy_op_sign(idx)?.0 == '+' && !sum_idx_vec.contains(&idx) {
+            sum_idx_vec.push(idx);
+        }
+    }
+
+    let mut num_simplified = 0;
+    for idx in sum_idx_vec {
+        // sums under an already simplified one may have been freed
+        if arena.copy_op_sign(idx).map(|(op, _)| op) != Ok('+') {
+            continue;
+        }
+        let simplified = if arena.is_mba(idx)? {
+            simplify_mba(arena, idx)?
+        } else if arena.is_poly_mba(idx)? {
+            simplify_poly(arena, idx)?
+        } else {
+            simplify_abstracted(arena, idx)?
+        };
+        if simplified {
+            num_simplified += 1;
+        }
+    }
+
+    Ok(num_simplified)
+}
+
+// Passes made by simplify_fixpoint. No fixpoint means that the last pass
+// still changed the expression.
+#[derive(Debug, PartialEq)]
+pub struct FixpointReport {
+    pub num_simplified: usize,
+    pub num_passes: usize,
+    pub fixpoint: bool,
+}
+
+// Default max num of passes of simplify_fixpoint
+pub const MAX_PASSES: usize = 16;
+
+// Bottom-up driver for MBA nested in MBA: nodes are visited in post-order,
+// each one reclassified from its (possibly rewritten) children before being
+// reduced, so that a parent sees the simplified form of its operands.
+// Passes are repeated until one changes nothing, at most max_passes times.
+pub fn simplify_fixpoint(
+    arena: &mut Arena,
+    root_idx: usize,
+    max_passes: usize,
+) -> Result<FixpointReport> {
+    let mut report = FixpointReport {
+        num_simplified: 0,
+        num_passes: 0,
+        fixpoint: false,
+    };
+
+    while report.num_passes < max_passes {
+        report.num_passes += 1;
+
+        // descendants before their parents, shared nodes once; handles
+        // detect the nodes freed by the rewrites of the pass
+        let mut node_ids = Vec::new();
+        for idx in arena.get_preorder(root_idx, true)?.into_iter().rev() {
+            let id = arena.elem_id(idx)?;
+            if !node_ids.contains(&id) {
+                node_ids.push(id);
+            }
+        }
+
+        let mut changed = false;
+        for id in node_ids {
+            let idx = match id.slot(arena) {
+                Ok(idx) => idx,
+                Err(_) => continue,
+            };
+            arena.reclassify(idx)?;
+
+            let simplified = if arena.is_mba(idx)? {
+                simplify_mba(arena, idx)?
+            } else if arena.is_poly_mba(idx)? || arena.is_product(idx)? {
+                simplify_poly(arena, idx)?
+            } else if arena.copy_op_sign(idx)?.0 == '+' {
+                simplify_abstracted(arena, idx)?
+            } else {
+                false
+            };
+            if simplified {
+                report.num_simplified += 1;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            report.fixpoint = true;
+            break;
+        }
+    }
+
+    Ok(report)
+}
+
+// Subterm abstracted by a fresh variable: (op, cst, children), unsigned
+pub(crate) type Core = (char, Option<u64>, Vec<usize>);
+
+fn simplify_abstracted(arena: &mut Arena, idx: usize) -> Result<bool> {
+    let mut fresh: Vec<(usize, Core)> = Vec::new();
+    let abs_idx = abstract_term(arena, idx, "+", "^|&.", &mut fresh)?;
+    if fresh.is_empty() {
+        return Ok(false);
+    }
+
+    if arena.is_mba(abs_idx)? {
+        simplify_mba(arena, abs_idx)?;
+    } else if arena.is_poly_mba(abs_idx)? {
+        simplify_poly(arena, abs_idx)?;
+    } else {
+        arena.remove_subtree(abs_idx)?;
+        return Ok(false);
+    }
+    let new_idx = substitute(arena, abs_idx, &fresh)?;
+
+    // fresh variables used more often than in the original sum may give a
+    // longer expression
+    let shorter = new_idx != idx
+        && arena.get_preorder(new_idx, false)?.len() < arena.get_preorder(idx, false)?.len();
+    if shorter {
+        arena.replace(idx, new_idx)?;
+    } else if new_idx != idx {
+        arena.remove_subtree(new_idx)?;
+    }
+    if new_idx != abs_idx {
+        arena.remove_subtree(abs_idx)?;
+    }
+
+    Ok(shorter)
+}
 
-                //let vars = arena.gather_vars(ch_idx);
-                let pn = arena.pn(ch_idx);
-                let (tt, n_vars) = compute_truth_table(&pn);
+// Copy of the term at idx, its non-bitwise subterms replaced by fresh
+// variables: under a sum, the sum_ops are kept, under them bitwise ops only
+pub(crate) fn abstract_term(
+    arena: &mut Arena,
+    idx: usize,
+    kept_ops: &str,
+    sum_ops: &str,
+    fresh: &mut Vec<(usize, Core)>,
+) -> Result<usize> {
+    if let Elem::Leaf(_) = arena.get(idx)? {
+        return Ok(idx);
+    }
 
-                solve_sierpinski(tt, n_vars);
+    let (op, sign) = arena.copy_op_sign(idx)?;
+    let (cst, ch) = (arena.get_cst(idx)?, arena.get_ch(idx)?);
+    if !kept_ops.contains(op) {
+        let core = (op, cst, ch);
+        let val = match fresh.iter().find(|(_, c)| *c == core) {
+            Some(&(val, _)) => val,
+            None => {
+                let val = arena.intern(&format!("#{}", fresh.len()));
+                fresh.push((val, core));
+                val
+            }
+        };
+        return Ok(arena.leaf(VarTerm { val, sign })?);
+    }
+
+    let ch_ops = if op == '+' { sum_ops } else { "^|&" };
+    let new_ch = ch
+        .iter()
+        .map(|&ch_idx| abstract_term(arena, ch_idx, ch_ops, sum_ops, fresh))
+        .collect::<Result<Vec<usize>>>()?;
+    Ok(arena.node_with(sign, op, cst, &new_ch)?)
+}
+
+// Copy of the term at idx, fresh variables replaced by their subterms. An
+// unsigned sum put back in a sum is merged in it.
+fn substitute(arena: &mut Arena, idx: usize, fresh: &[(usize, Core)]) -> Result<usize> {
+    let core_of = |arena: &Arena, idx: usize| -> Result<Option<(String, &Core)>> {
+        if let Elem::Leaf(l) = arena.get(idx)? {
+            if let Some((_, core)) = fresh.iter().find(|(val, _)| *val == l.val.val) {
+                return Ok(Some((l.val.sign.clone(), core)));
             }
         }
+        Ok(None)
+    };
+
+    if let Some((sign, (op, cst, ch))) = core_of(arena, idx)? {
+        return Ok(arena.node_with(sign, *op, *cst, ch)?);
     }
+
+    let (op, sign) = match arena.get(idx)? {
+        Elem::Leaf(_) => return Ok(idx),
+        Elem::Node(_) => arena.copy_op_sign(idx)?,
+        Elem::Free => return Err(ArenaError::FreeElem().into()),
+    };
+    let (mut cst, ch) = (arena.get_cst(idx)?, arena.get_ch(idx)?);
+    let mut new_ch = Vec::new();
+    for ch_idx in ch.iter() {
+        match core_of(arena, *ch_idx)? {
+            Some((ch_sign, ('+', ch_cst, ch_ch))) if op == '+' && ch_sign.is_empty() => {
+                new_ch.extend(ch_ch.iter().cloned());
+                if let Some(c) = *ch_cst {
+                    cst = Some(compute_op('+', cst.unwrap_or(0), c, arena.width()));
+                }
+            }
+            _ => new_ch.push(substitute(arena, *ch_idx, fresh)?),
+        }
+    }
+    if new_ch == ch && cst == arena.get_cst(idx)? {
+        return Ok(idx);
+    }
+
+    Ok(arena.node_with(sign, op, cst, &new_ch)?)
 }
 
-// TODO: genericity on int type depending on number of vars
-pub fn bit_pop(n: u64) -> u8 {
-    (0..u64::N_BITS).map(|b| n.bit(b) as u8).sum()
+fn is_reducible(arena: &Arena, idx: usize) -> Result<bool> {
+    Ok(arena.is_mba(idx)? || arena.is_poly_mba(idx)? || arena.is_product(idx)?)
 }
 
-pub fn solve_sierpinski(tt: Vec<i8>, num_vars: u8) -> Vec<i8> {
-    let mut a = vec![tt[0]];
-    for exp in 0..(num_vars) {
-        let p2: u64 = 2 << exp;
-        let mid = (p2 / 2) as u64;
+fn simplify_mba(arena: &mut Arena, idx: usize) -> Result<bool> {
+    let (mut inputs, sig) = match linear_signature(arena, idx)? {
+        Some(res) => res,
+        None => return Ok(false),
+    };
+
+    let width = arena.width();
+    let mut coeffs = sig.clone();
+    mobius_transform(&mut coeffs)?;
+    for c in coeffs.iter_mut() {
+        *c = to_signed(*c as u64, width);
+    }
+
+    let cand = linear_candidates(&sig, &coeffs, &mut inputs, width)?;
+    let sign = arena.get_sign(idx)?;
+    replace_if_shorter(arena, idx, cand, &inputs, sign)
+}
 
-        for i in 0..mid {
-            let mut a_i = -a[i as usize];
-            println!("mid: {}", mid);
-            println!("{:?}", comb_bitmask(exp, i));
-            for b in comb_bitmask(exp, i) {
-                let b_xor_i = b ^ i;
-                let s_pos = bit_pop(b_xor_i) % 2 == 0;
-                let sign = (if s_pos { 1 } else { -1 }) as i8;
-                a_i += tt[(mid + b) as usize] * sign;
+// Signature of a linear MBA or bitwise expression: value of the sum of its
+// operands on each row of their truth tables, with the cst of the node as
+// -cst times the all-ones function. Values are modulo 2^width, kept signed.
+// None if idx is neither, or its operands can't be tabulated.
+pub(crate) fn linear_signature(
+    arena: &Arena,
+    idx: usize,
+) -> Result<Option<(Vec<Input>, Vec<i64>)>> {
+    let mut operands = Vec::new();
+    if arena.is_mba(idx)? {
+        for ch_idx in arena.get_ch(idx)? {
+            match mba_operand(arena, ch_idx)? {
+                Some(op) => operands.push(op),
+                None => return Ok(None),
             }
+        }
+    } else if arena.is_bitwise(idx)? {
+        match mba_operand(arena, idx)? {
+            Some(op) => operands.push(op),
+            None => return Ok(None),
+        }
+    } else {
+        return Ok(None);
+    }
+
+    let bitw: Vec<(usize, bool)> = operands.iter().map(|&(_, b, neg)| (b, neg)).collect();
+    let (inputs, tts) = match operand_tables(arena, idx, &bitw)? {
+        Some(res) => res,
+        None => return Ok(None),
+    };
+
+    let width = arena.width();
+    let cst = match arena.is_mba(idx)? {
+        true => arena.get_cst(idx)?.map_or(0, |c| to_signed(c, width)),
+        false => 0,
+    };
+    let mut sig = vec![cst.wrapping_neg(); 1 << inputs.len()];
+    for ((coef, _, _), tt) in operands.iter().zip(tts.iter()) {
+        for (i, c) in sig.iter_mut().enumerate() {
+            if tt.get(i) {
+                *c = c.wrapping_add(*coef);
+            }
+        }
+    }
+    for v in sig.iter_mut() {
+        *v = to_signed(*v as u64, width);
+    }
+
+    Ok(Some((inputs, sig)))
+}
+
+// Polynomial in the conjunction basis: a monomial is the sorted list of the
+// basis indices it multiplies, the empty one being the cst term
+type Poly = BTreeMap<Vec<usize>, i64>;
+
+// Bitwise operands (idx, negated) multiplied in a polynomial MBA term
+type Factors = Vec<(usize, bool)>;
+
+fn simplify_poly(arena: &mut Arena, idx: usize) -> Result<bool> {
+    let width = arena.width();
+
+    // a sum keeps its sign above the result, the sign of a single product
+    // goes in its coefficient
+    let (op, sign) = arena.copy_op_sign(idx)?;
+    let (ch, sign, cst) = if op == '+' {
+        (arena.get_ch(idx)?, sign, arena.get_cst(idx)?)
+    } else {
+        (vec![idx], String::new(), None)
+    };
 
-            a.push(a_i);
+    // terms as coef * (product of factors), the cst being a term without
+    // factors
+    let mut terms: Vec<(i64, Factors)> = Vec::new();
+    if let Some(cst) = cst {
+        terms.push((to_signed(cst, width), Vec::new()));
+    }
+    for ch_idx in ch {
+        match poly_operand(arena, ch_idx)? {
+            Some(term) => terms.push(term),
+            None => return Ok(false),
+        }
+    }
+    let factors: Factors = terms.iter().flat_map(|(_, f)| f.iter().cloned()).collect();
+    let (mut inputs, tts) = match operand_tables(arena, idx, &factors)? {
+        Some(res) => res,
+        None => return Ok(false),
+    };
+
+    let mut tts = tts.into_iter();
+    let mut poly = Poly::new();
+    for (coef, factors) in terms {
+        let mut prod: Poly = std::iter::once((Vec::new(), coef)).collect();
+        for tt in tts.by_ref().take(factors.len()) {
+            let mut basis: Vec<i64> = (0..tt.num_rows()).map(|i| tt.get(i) as i64).collect();
+            mobius_transform(&mut basis)?;
+            prod = mul_basis(&prod, &basis);
+        }
+        for (m, c) in prod {
+            let e = poly.entry(m).or_insert(0);
+            *e = e.wrapping_add(c);
         }
     }
-    a.reverse();
-    let mut res: Vec<i8> = a.iter().map(|v| -v).collect();
-    res.push(1);
+    for c in poly.values_mut() {
+        *c = to_signed(*c as u64, width);
+    }
+    poly.retain(|_, c| *c != 0);
+
+    // without products left, this is a linear mba
+    let cand = if poly.keys().all(|m| m.len() <= 1) {
+        let mut coeffs = vec![0i64; 1 << inputs.len()];
+        for (m, &c) in poly.iter() {
+            match m.first() {
+                Some(&i) => coeffs[i] = c,
+                None => coeffs[0] = c.wrapping_neg(),
+            }
+        }
+        let mut sig = coeffs.clone();
+        inverse_mobius_transform(&mut sig)?;
+        for v in sig.iter_mut() {
+            *v = to_signed(*v as u64, width);
+        }
+        linear_candidates(&sig, &coeffs, &mut inputs, width)?
+    } else {
+        vec![poly
+            .into_iter()
+            .map(|(m, c)| (c, m.into_iter().filter_map(BoolExpr::conj).collect()))
+            .collect()]
+    };
 
+    replace_if_shorter(arena, idx, cand, &inputs, sign)
+}
+
+// Product of a polynomial by a combination over the conjunction basis,
+// whose all-ones function (index 0) is the cst -1
+fn mul_basis(poly: &Poly, basis: &[i64]) -> Poly {
+    let mut res = Poly::new();
+    for (m, &c) in poly {
+        for (i, &a) in basis.iter().enumerate().filter(|(_, &a)| a != 0) {
+            let mut m = m.clone();
+            let a = if i == 0 {
+                a.wrapping_neg()
+            } else {
+                m.push(i);
+                m.sort_unstable();
+                a
+            };
+            let e = res.entry(m).or_insert(0);
+            *e = e.wrapping_add(c.wrapping_mul(a));
+        }
+    }
     res
 }
 
-// Given a bit representation (of len n)...
-// ... output all combination of its bit population
-pub fn comb_bitmask(num_bits: u8, n: u64) -> Vec<u64> {
-    println!("{}", format!("num_bits: {:?}-{}", num_bits, n));
-    let bit_base: Vec<u8> = (0..num_bits)
-        .filter_map(|b| if n.bit(b) { Some(b) } else { None })
-        .collect();
+// Sum of coef * (product of bitwise exprs), a term without factors being
+// a cst
+type Comb = Vec<(i64, Vec<BoolExpr>)>;
+
+// Candidates of a linear mba: combination over the conjunction basis and,
+// if the signature takes two values a and b, -a + (b-a) * f with f the
+// synthesized bitwise expr true where the signature is b. With csts among
+// the inputs, the semi-linear combination is one more candidate.
+fn linear_candidates(
+    sig: &[i64],
+    coeffs: &[i64],
+    inputs: &mut Vec<Input>,
+    width: u8,
+) -> Result<Vec<Comb>> {
+    let num_inputs = inputs.len();
+    // a cst is a term without factors
+    let cst_term = |c: i64| Some((to_signed(c as u64, width), Vec::new())).filter(|t| t.0 != 0);
 
-    let bit_pop = bit_base.len();
-    (0..(bit_pop + 1))
-        .map(|n_b| {
-            bit_base
+    let mut cand: Vec<Comb> = vec![cst_term(coeffs[0].wrapping_neg())
+        .into_iter()
+        .chain(
+            coeffs
                 .iter()
-                .combinations(n_b)
-                .map(|comb| {
-                    let mut i: u64 = 0;
-                    for b in comb {
-                        i.set_bit(*b, true);
-                    }
-                    i
-                })
-                .collect::<Vec<u64>>()
+                .enumerate()
+                .skip(1)
+                .filter(|(_, &c)| c != 0)
+                .filter_map(|(i, &c)| Some((c, vec![BoolExpr::conj(i)?]))),
+        )
+        .collect()];
+
+    if let Some(terms) = semi_linear_terms(sig, inputs, width)? {
+        cand.push(terms);
+    }
+
+    let mut values = sig.to_vec();
+    values.sort_unstable();
+    values.dedup();
+    if values.len() > 2 {
+        return Ok(cand);
+    }
+    // a zero base first, without cst
+    values.sort_by_key(|&a| a != 0);
+    for a in values {
+        let (mut tt, mut b) = (TruthTable::new(num_inputs as u8)?, None);
+        for (i, &v) in sig.iter().enumerate() {
+            if v != a {
+                tt.set(i, true);
+                b = Some(v);
+            }
+        }
+
+        let mut terms: Comb = cst_term(a.wrapping_neg()).into_iter().collect();
+        if let Some(b) = b {
+            match synth::synthesize(&tt, Cost::Nodes) {
+                Some(e) => terms.push((to_signed(b.wrapping_sub(a) as u64, width), vec![e])),
+                None => continue,
+            }
+        }
+        cand.push(terms);
+    }
+
+    Ok(cand)
+}
+
+// Semi-linear combination: on a bit position, each cst input is a bit
+// constant, so the bitwise operands are functions of the variables only.
+// Positions are grouped by the bits of the csts there, each group solved
+// over the conjunction basis, and the conjunctions masked by the positions
+// sharing a coefficient: (x & 0xf0) + (x & 0x0f) = x & 0xff.
+// The masks are appended to the inputs, as csts.
+fn semi_linear_terms(sig: &[i64], inputs: &mut Vec<Input>, width: u8) -> Result<Option<Comb>> {
+    let num_vars = inputs
+        .iter()
+        .take_while(|i| matches!(i, Input::Var(_)))
+        .count();
+    let csts: Vec<u64> = inputs[num_vars..]
+        .iter()
+        .filter_map(|i| match i {
+            Input::Cst(c) => Some(*c),
+            Input::Var(_) => None,
         })
-        .flatten()
-        .collect()
+        .collect();
+    if csts.is_empty() {
+        return Ok(None);
+    }
+
+    // mask of the positions of each bit pattern of the csts
+    let mut groups: BTreeMap<usize, u64> = BTreeMap::new();
+    for k in 0..width {
+        let pattern = csts
+            .iter()
+            .enumerate()
+            .fold(0, |p, (j, c)| p | (c.bit(k) as usize) << j);
+        *groups.entry(pattern).or_insert(0) |= 1 << k;
+    }
+
+    // mask of the positions of each (conjunction, coefficient)
+    let mut masks: BTreeMap<(usize, i64), u64> = BTreeMap::new();
+    for (&pattern, &mask) in groups.iter() {
+        let mut coeffs: Vec<i64> = (0..1 << num_vars)
+            .map(|i| sig[i | pattern << num_vars])
+            .collect();
+        mobius_transform(&mut coeffs)?;
+        for (i, &c) in coeffs.iter().enumerate() {
+            let c = to_signed(c as u64, width);
+            if c != 0 {
+                *masks.entry((i, c)).or_insert(0) |= mask;
+            }
+        }
+    }
+
+    // the all-ones function on a mask is the mask itself
+    let (mut cst, mut terms) = (0, Vec::new());
+    for ((i, c), mask) in masks {
+        let conj = match BoolExpr::conj(i) {
+            Some(conj) => conj,
+            None => {
+                cst = compute_op('+', cst, compute_op('.', c as u64, mask, width), width);
+                continue;
+            }
+        };
+        if mask == width_mask(width) {
+            terms.push((c, vec![conj]));
+            continue;
+        }
+
+        let pos = match inputs
+            .iter()
+            .position(|i| matches!(i, Input::Cst(m) if *m == mask))
+        {
+            Some(pos) => pos,
+            None => {
+                inputs.push(Input::Cst(mask));
+                inputs.len() - 1
+            }
+        };
+        if pos > u8::MAX as usize {
+            return Ok(None);
+        }
+        terms.push((c, vec![BoolExpr::op('&', conj, BoolExpr::Var(pos as u8))]));
+    }
+    if cst != 0 {
+        terms.insert(0, (to_signed(cst, width), Vec::new()));
+    }
+
+    Ok(Some(terms))
 }
 
-pub fn compute_truth_table(pn: &Vec<(char, String)>) -> (Vec<i8>, u8) {
-    if pn.len() == 0 {
-        panic!("empty polish notation");
+fn replace_if_shorter(
+    arena: &mut Arena,
+    idx: usize,
+    cand: Vec<Comb>,
+    inputs: &[Input],
+    sign: String,
+) -> Result<bool> {
+    let mut best = (arena.get_preorder(idx, false)?.len(), None);
+    for terms in cand {
+        // hash-consing may give back the node itself or a previous candidate
+        let new_idx = build_comb(arena, &terms, inputs, sign.clone())?;
+        if new_idx == idx || best.1 == Some(new_idx) {
+            continue;
+        }
+        let new_len = arena.get_preorder(new_idx, false)?.len();
+        let old_idx = if new_len < best.0 {
+            best.1.replace(new_idx)
+        } else {
+            Some(new_idx)
+        };
+        best.0 = best.0.min(new_len);
+
+        if let Some(old_idx) = old_idx {
+            arena.remove_subtree(old_idx)?;
+        }
     }
 
-    let vars: BTreeSet<char> = pn
-        .into_iter()
-        .filter_map(|(c, _)| if "^|&!".contains(*c) { None } else { Some(*c) })
+    if let (_, Some(new_idx)) = best {
+        arena.replace(idx, new_idx)?;
+        return Ok(true);
+    }
+
+    Ok(false)
+}
+
+// Common inputs of bitwise operands, with the truth table of each
+type Tables = (Vec<Input>, Vec<TruthTable>);
+
+// Truth tables of the bitwise operands (idx, negated) on common inputs:
+// the variables under idx, then the csts of the operands. None if an
+// operand is not purely bitwise or if there are too many inputs
+fn operand_tables(arena: &Arena, idx: usize, operands: &[(usize, bool)]) -> Result<Option<Tables>> {
+    // ordered by name, ids follow the parsing order
+    let mut named = Vec::new();
+    for v in arena.gather_vars(idx)? {
+        named.push((arena.var_name(v)?, v));
+    }
+    named.sort();
+    let vars: Vec<usize> = named.into_iter().map(|(_, v)| v).collect();
+    if vars.len() > MAX_NUM_VARS as usize {
+        return Ok(None);
+    }
+
+    let mut pns = Vec::new();
+    let mut csts: Vec<u64> = Vec::new();
+    for &(bitw_idx, neg) in operands {
+        let mut pn = arena.pn(bitw_idx)?;
+        if pn[..pn.len() - 1]
+            .iter()
+            .any(|(_, sign)| !sign.is_empty() && sign != "~")
+        {
+            return Ok(None);
+        }
+        if let Some((_, sign)) = pn.last_mut() {
+            *sign = if neg { "~".to_string() } else { String::new() };
+        }
+
+        for (e, _) in pn.iter() {
+            if let PnElem::Cst(cst) = e {
+                if !csts.contains(cst) {
+                    csts.push(*cst);
+                }
+            }
+        }
+        pns.push(pn);
+    }
+
+    // csts of the bitwise operands stand as pseudo-variables, placed after
+    // the variables: identities found hold for any value of them
+    csts.sort_unstable();
+    if vars.len() + csts.len() > MAX_NUM_VARS as usize {
+        return Ok(None);
+    }
+    let num_symbols = arena.symbols().len();
+    let var_ids: Vec<usize> = vars
+        .iter()
+        .cloned()
+        .chain((0..csts.len()).map(|pos| num_symbols + pos))
+        .collect();
+    let inputs: Vec<Input> = vars
+        .iter()
+        .map(|&v| Input::Var(v))
+        .chain(csts.iter().map(|&c| Input::Cst(c)))
         .collect();
 
-    let num_var = vars.len() as u8;
-    if num_var > 3 {
-        panic!(format!("num of vars: {} is too large for now", num_var))
+    let mut tts = Vec::new();
+    for mut pn in pns {
+        for (e, _) in pn.iter_mut() {
+            if let PnElem::Cst(cst) = e {
+                if let Some(pos) = csts.iter().position(|c| c == cst) {
+                    *e = PnElem::Var(num_symbols + pos);
+                }
+            }
+        }
+        tts.push(compute_truth_table_vars(&pn, &var_ids)?);
     }
 
-    let base: i32 = 2;
-    let mut res = vec![];
-    for i in 0..(base.pow(num_var as u32)) {
-        let bool_map: BTreeMap<char, bool> = vars
-            .clone()
-            .into_iter()
-            .enumerate()
-            .map(|(pos, v)| (v, i.bit(pos)))
-            .collect();
+    Ok(Some((inputs, tts)))
+}
+
+fn split_sign(sign: &str) -> Option<(i64, bool)> {
+    match sign {
+        "" => Some((1, false)),
+        "~" => Some((1, true)),
+        "-" => Some((-1, false)),
+        "-~" => Some((-1, true)),
+        _ => None,
+    }
+}
 
-        let mut curr_pn = pn.clone();
-
-        println!("START - {:?}", bool_map);
-
-        let mut aux_op = '-';
-        let mut aux_b: Option<bool> = Some(true);
-        let mut stack = vec![];
-        while let Some((c, sign)) = curr_pn.pop() {
-            if sign.len() > 0 && sign != "~" {
-                panic!("arithm. sign - in bitwise formula");
-            }
-
-            let bool_sign = sign == "~";
-
-            match c {
-                '^' | '|' | '&' | '!' => {
-                    let mut val = aux_b.expect("should have found bool!");
-                    match c {
-                        '^' | '|' | '&' => {
-                            if aux_op != '-' {
-                                stack.push((val, aux_op, bool_sign));
-                            }
-                            aux_op = c;
-                            aux_b = None;
-                        }
-                        '!' => {
-                            let (par_val, par_op, par_bool_s) = stack
-                                .pop()
-                                .expect("should have found stacked parent (value, op)");
-                            val ^= par_bool_s;
-                            aux_b = Some(compute_bool(par_op, par_val, val));
-                            aux_op = par_op;
-                        }
-                        _ => unreachable!(),
+// Operand of an MBA node as (coef, bitwise idx, negated), such that its
+// value is coef * f, f being the function at bitwise idx (~f if negated)
+fn mba_operand(arena: &Arena, idx: usize) -> Result<Option<(i64, usize, bool)>> {
+    if !arena.is_mba_term(idx)? {
+        let sign = arena.get_sign(idx)?;
+        return Ok(split_sign(&sign).map(|(coef, neg)| (coef, idx, neg)));
+    }
+
+    let (_, sign) = arena.copy_op_sign(idx)?;
+    let term_coef = match split_sign(&sign) {
+        Some((coef, false)) => coef,
+        _ => return Ok(None),
+    };
+
+    let bitw_idx = match arena.get_ch(idx)?.first() {
+        Some(&bitw_idx) => bitw_idx,
+        None => return Ok(None),
+    };
+    let (bitw_coef, bitw_neg) = match mba_operand(arena, bitw_idx)? {
+        Some((coef, _, neg)) => (coef, neg),
+        None => return Ok(None),
+    };
+    let cst = match arena.get_cst(idx)? {
+        Some(cst) => to_signed(cst, arena.width()),
+        None => return Ok(None),
+    };
+
+    Ok(Some((
+        term_coef.wrapping_mul(bitw_coef).wrapping_mul(cst),
+        bitw_idx,
+        bitw_neg,
+    )))
+}
+
+// Term of a polynomial MBA as (coef, factors), factors being bitwise
+// operands (idx, negated) as for mba_operand
+fn poly_operand(arena: &Arena, idx: usize) -> Result<Option<(i64, Factors)>> {
+    if !arena.is_product(idx)? {
+        let operand = mba_operand(arena, idx)?;
+        return Ok(operand.map(|(coef, bitw_idx, neg)| (coef, vec![(bitw_idx, neg)])));
+    }
+
+    let (_, sign) = arena.copy_op_sign(idx)?;
+    let mut coef = match split_sign(&sign) {
+        Some((coef, false)) => coef,
+        _ => return Ok(None),
+    };
+    if let Some(cst) = arena.get_cst(idx)? {
+        coef = coef.wrapping_mul(to_signed(cst, arena.width()));
+    }
+
+    let mut factors = Vec::new();
+    for ch_idx in arena.get_ch(idx)? {
+        match split_sign(&arena.get_sign(ch_idx)?) {
+            Some((ch_coef, neg)) => {
+                coef = coef.wrapping_mul(ch_coef);
+                factors.push((ch_idx, neg));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some((coef, factors)))
+}
+
+// Input of a truth table position: a variable, or a cst standing as a
+// pseudo-variable
+#[derive(Clone, Copy)]
+pub(crate) enum Input {
+    Var(usize),
+    Cst(u64),
+}
+
+// Inserted bitwise expression, a cst if all its inputs are csts
+enum Operand {
+    Idx(usize),
+    Cst(u64),
+}
+
+fn insert_bool(
+    arena: &mut Arena,
+    e: &BoolExpr,
+    inputs: &[Input],
+    mut sign: String,
+) -> Result<Operand> {
+    let width = arena.width();
+    match e {
+        BoolExpr::Var(pos) => match inputs.get(*pos as usize) {
+            Some(&Input::Var(val)) => Ok(Operand::Idx(arena.leaf(VarTerm { val, sign })?)),
+            Some(&Input::Cst(cst)) => Ok(Operand::Cst(compute_sign(cst, &sign, width)?)),
+            None => Err(ArenaError::UnknownVar(*pos as usize).into()),
+        },
+        BoolExpr::Not(e) => {
+            sign.push('~');
+            insert_bool(arena, e, inputs, sign)
+        }
+        BoolExpr::Op(op, _, _) => {
+            let (mut ch, mut cst) = (Vec::new(), None);
+            for e in e.operands(*op).iter().rev() {
+                match insert_bool(arena, e, inputs, String::new())? {
+                    Operand::Idx(idx) => ch.push(idx),
+                    Operand::Cst(c) => {
+                        cst = Some(cst.map_or(c, |old| compute_op(*op, old, c, width)));
                     }
                 }
-                var => {
-                    let var_bool =
-                        bool_sign ^ bool_map.get(&var).expect("should have found variable");
-
-                    if let Some(b) = aux_b {
-                        aux_b = Some(compute_bool(aux_op, b, var_bool));
-                    } else {
-                        aux_b = Some(var_bool);
-                    }
+            }
+
+            match (ch.is_empty(), cst) {
+                (true, Some(cst)) => Ok(Operand::Cst(compute_sign(cst, &sign, width)?)),
+                _ => Ok(Operand::Idx(arena.node_with(sign, *op, cst, &ch)?)),
+            }
+        }
+    }
+}
+
+fn split_coef(coef: i64) -> (String, u64) {
+    if coef < 0 {
+        ("-".to_string(), coef.wrapping_neg() as u64)
+    } else {
+        (String::new(), coef as u64)
+    }
+}
+
+fn build_comb(arena: &mut Arena, terms: &Comb, inputs: &[Input], sign: String) -> Result<usize> {
+    let width = arena.width();
+    // terms of csts only are summed in the cst of the node
+    let mut cst: Option<u64> = None;
+    let mut add_cst = |c: u64| cst = Some(cst.map_or(c, |old| compute_op('+', old, c, width)));
+
+    let mut terms_idx = Vec::new();
+    for (coef, factors) in terms.iter().rev() {
+        if let [e] = &factors[..] {
+            let (coef_sign, abs_coef) = split_coef(*coef);
+            if abs_coef == 1 {
+                match insert_bool(arena, e, inputs, coef_sign)? {
+                    Operand::Idx(term_idx) => terms_idx.push(term_idx),
+                    Operand::Cst(c) => add_cst(c),
+                }
+                continue;
+            }
+
+            match insert_bool(arena, e, inputs, String::new())? {
+                Operand::Idx(term_idx) => {
+                    terms_idx.push(arena.node_with(coef_sign, '.', Some(abs_coef), &[term_idx])?)
                 }
+                Operand::Cst(c) => add_cst(compute_op('.', *coef as u64, c, width)),
+            }
+            continue;
+        }
+
+        // factors of csts only go in the coefficient
+        let (mut prod, mut ch) = (*coef as u64 & width_mask(width), Vec::new());
+        for e in factors.iter().rev() {
+            match insert_bool(arena, e, inputs, String::new())? {
+                Operand::Idx(factor_idx) => ch.push(factor_idx),
+                Operand::Cst(c) => prod = compute_op('.', prod, c, width),
+            }
+        }
+        let (coef_sign, abs_coef) = split_coef(to_signed(prod, width));
+        match ch.len() {
+            _ if prod == 0 => (),
+            0 => add_cst(prod),
+            1 if abs_coef == 1 && coef_sign.is_empty() => terms_idx.push(ch[0]),
+            1 => terms_idx.push(arena.node_with(coef_sign, '.', Some(abs_coef), &ch)?),
+            _ => {
+                let abs_coef = Some(abs_coef).filter(|&c| c != 1);
+                terms_idx.push(arena.node_with(coef_sign, '.', abs_coef, &ch)?)
+            }
+        }
+    }
+
+    let cst = match cst {
+        Some(0) | None if terms_idx.is_empty() => Some(0),
+        Some(0) => None,
+        cst => cst,
+    };
+    if let ([term_idx], true, None) = (&terms_idx[..], sign.is_empty(), cst) {
+        return Ok(*term_idx);
+    }
+
+    Ok(arena.node_with(sign, '+', cst, &terms_idx)?)
+}
+
+// In place Möbius transform (butterfly), over integers mod 2^64.
+// From the values of a function on all rows, gives its coefficients over the
+// basis of conjunctions: v[i] is then the coefficient of the conjunction of
+// the variables whose position is set in i.
+pub fn mobius_transform(v: &mut [i64]) -> Result<()> {
+    check_power_of_two(v.len())?;
+    butterfly(v, i64::wrapping_sub);
+    Ok(())
+}
+
+// Inverse of mobius_transform: from the coefficients over the basis of
+// conjunctions, gives the values of the function on all rows.
+pub fn inverse_mobius_transform(v: &mut [i64]) -> Result<()> {
+    check_power_of_two(v.len())?;
+    butterfly(v, i64::wrapping_add);
+    Ok(())
+}
+
+fn check_power_of_two(len: usize) -> Result<()> {
+    if !len.is_power_of_two() {
+        return Err(Error::NotPowerOfTwo(len));
+    }
+    Ok(())
+}
+
+// len of v should be a power of 2
+fn butterfly<F>(v: &mut [i64], func: F)
+where
+    F: Fn(i64, i64) -> i64,
+{
+    let mut h = 1;
+    while h < v.len() {
+        for i in 0..v.len() {
+            if i & h != 0 {
+                v[i] = func(v[i], v[i ^ h]);
+            }
+        }
+        h <<= 1;
+    }
+}
+
+pub const MAX_NUM_VARS: u8 = 16;
+
+// Variable patterns inside a 64-rows word, for the first 6 variables
+const VAR_WORDS: [u64; 6] = [
+    0xaaaa_aaaa_aaaa_aaaa,
+    0xcccc_cccc_cccc_cccc,
+    0xf0f0_f0f0_f0f0_f0f0,
+    0xff00_ff00_ff00_ff00,
+    0xffff_0000_ffff_0000,
+    0xffff_ffff_0000_0000,
+];
+
+// Truth table of a boolean function, packed 64 rows per word.
+// Row i gives the value of the function when variable of position p
+// is set to the bit p of i.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TruthTable {
+    num_vars: u8,
+    words: Vec<u64>,
+}
+
+impl TruthTable {
+    pub fn new(num_vars: u8) -> Result<Self> {
+        if num_vars > MAX_NUM_VARS {
+            return Err(Error::TooManyVars(num_vars as usize));
+        }
+
+        Ok(TruthTable::zeros(num_vars))
+    }
+
+    // num_vars should be at most MAX_NUM_VARS
+    fn zeros(num_vars: u8) -> Self {
+        let num_words = (1usize << num_vars).div_ceil(64);
+        TruthTable {
+            num_vars,
+            words: vec![0; num_words],
+        }
+    }
+
+    // Truth table of the variable of position pos
+    pub fn var(num_vars: u8, pos: u8) -> Result<Self> {
+        if pos >= num_vars {
+            return Err(ArenaError::UnknownVar(pos as usize).into());
+        }
+
+        let mut tt = TruthTable::new(num_vars)?;
+        for (k, w) in tt.words.iter_mut().enumerate() {
+            *w = if pos < 6 {
+                VAR_WORDS[pos as usize]
+            } else if k.bit(pos - 6) {
+                !0
+            } else {
+                0
             };
         }
+        tt.mask_last();
+
+        Ok(tt)
+    }
+
+    pub fn from_rows(rows: &[u8]) -> Result<Self> {
+        check_power_of_two(rows.len())?;
+
+        let num_vars = rows.len().trailing_zeros();
+        if num_vars > MAX_NUM_VARS as u32 {
+            return Err(Error::TooManyVars(num_vars as usize));
+        }
 
-        let mut b = aux_b.expect("should have a bool value");
-        while let Some((par_val, par_op, par_bool_s)) = stack.pop() {
-            b = compute_bool(par_op, par_val, b) ^ par_bool_s;
+        let mut tt = TruthTable::zeros(num_vars as u8);
+        for (i, r) in rows.iter().enumerate() {
+            tt.set(i, *r != 0);
         }
 
-        println!("aux_b {}", b);
-        res.push(b as i8);
+        Ok(tt)
+    }
+
+    pub fn num_vars(&self) -> u8 {
+        self.num_vars
+    }
+
+    pub fn num_rows(&self) -> usize {
+        1 << self.num_vars
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        self.words[i / 64].bit(i % 64)
+    }
+
+    pub fn set(&mut self, i: usize, b: bool) {
+        self.words[i / 64].set_bit(i % 64, b);
+    }
+
+    pub fn to_rows(&self) -> Vec<u8> {
+        (0..self.num_rows()).map(|i| self.get(i) as u8).collect()
+    }
+
+    // Some(b) if the function is the constant b
+    pub fn is_const(&self) -> Option<bool> {
+        let mut all_ones = TruthTable::zeros(self.num_vars);
+        all_ones.map_words(|_| !0);
+        if self.words.iter().all(|&w| w == 0) {
+            Some(false)
+        } else if *self == all_ones {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    pub fn depends_on(&self, pos: u8) -> bool {
+        pos < self.num_vars && self.cofactor(pos, false) != self.cofactor(pos, true)
+    }
+
+    // Function with the variable of position pos set to b, over the
+    // remaining variables. Unchanged if there is no such variable.
+    pub fn cofactor(&self, pos: u8, b: bool) -> TruthTable {
+        if pos >= self.num_vars {
+            return self.clone();
+        }
+
+        let mut res = TruthTable::zeros(self.num_vars - 1);
+        let low_mask = (1 << pos) - 1;
+        for i in 0..res.num_rows() {
+            let row = ((i & !low_mask) << 1) | ((b as usize) << pos) | (i & low_mask);
+            res.set(i, self.get(row));
+        }
+
+        res
+    }
+
+    fn map_words<F>(&mut self, func: F)
+    where
+        F: Fn(u64) -> u64,
+    {
+        for w in self.words.iter_mut() {
+            *w = func(*w);
+        }
+        self.mask_last();
+    }
+
+    fn zip_words<F>(&mut self, other: &TruthTable, func: F)
+    where
+        F: Fn(u64, u64) -> u64,
+    {
+        for (w, o) in self.words.iter_mut().zip(other.words.iter()) {
+            *w = func(*w, *o);
+        }
+    }
+
+    // unused rows (less than 64 rows) are kept to 0
+    fn mask_last(&mut self) {
+        if self.num_vars < 6 {
+            self.words[0] &= (1 << self.num_rows()) - 1;
+        }
+    }
+}
+
+impl Not for &TruthTable {
+    type Output = TruthTable;
+
+    fn not(self) -> TruthTable {
+        let mut res = self.clone();
+        res.map_words(|w| !w);
+        res
+    }
+}
+
+impl BitAnd for &TruthTable {
+    type Output = TruthTable;
+
+    fn bitand(self, other: &TruthTable) -> TruthTable {
+        let mut res = self.clone();
+        res.zip_words(other, |w1, w2| w1 & w2);
+        res
+    }
+}
+
+impl BitOr for &TruthTable {
+    type Output = TruthTable;
+
+    fn bitor(self, other: &TruthTable) -> TruthTable {
+        let mut res = self.clone();
+        res.zip_words(other, |w1, w2| w1 | w2);
+        res
+    }
+}
+
+impl BitXor for &TruthTable {
+    type Output = TruthTable;
+
+    fn bitxor(self, other: &TruthTable) -> TruthTable {
+        let mut res = self.clone();
+        res.zip_words(other, |w1, w2| w1 ^ w2);
+        res
+    }
+}
+
+pub fn compute_truth_table(pn: &[(PnElem, String)]) -> Result<(TruthTable, u8)> {
+    if pn.is_empty() {
+        return Err(Error::EmptyPn());
+    }
+
+    let vars: Vec<usize> = pn
+        .iter()
+        .filter_map(|(e, _)| match e {
+            PnElem::Var(v) => Some(*v),
+            _ => None,
+        })
+        .collect::<BTreeSet<usize>>()
+        .into_iter()
+        .collect();
+
+    Ok((compute_truth_table_vars(pn, &vars)?, vars.len() as u8))
+}
+
+// Same as compute_truth_table, over a given (ordered) set of variables
+// which may be larger than the one of the expression.
+pub fn compute_truth_table_vars(pn: &[(PnElem, String)], vars: &[usize]) -> Result<TruthTable> {
+    if vars.len() > MAX_NUM_VARS as usize {
+        return Err(Error::TooManyVars(vars.len()));
+    }
+
+    let num_var = vars.len() as u8;
+    let var_map: BTreeMap<usize, TruthTable> = vars
+        .iter()
+        .enumerate()
+        .map(|(pos, v)| (*v, TruthTable::var(num_var, pos as u8)))
+        .map(|(v, tt)| tt.map(|tt| (v, tt)))
+        .collect::<Result<_>>()?;
+
+    eval_pn(pn, &var_map)
+}
+
+// pn is read from its end: an op opens a frame, which is closed either by
+// Close or by the end of pn.
+// All rows are evaluated at once, 64 by 64.
+fn eval_pn(pn: &[(PnElem, String)], var_map: &BTreeMap<usize, TruthTable>) -> Result<TruthTable> {
+    type Frame = (char, Option<TruthTable>, bool);
+
+    fn push_val(stack: &mut [Frame], res: &mut Option<TruthTable>, val: TruthTable) -> Result<()> {
+        if let Some((op, acc, _)) = stack.last_mut() {
+            if let Some(acc_tt) = acc {
+                let op = *op;
+                if !matches!(op, '^' | '|' | '&') {
+                    return Err(ArenaError::UnknownOp(op).into());
+                }
+                acc_tt.zip_words(&val, |w1, w2| compute_bool(op, w1, w2));
+            } else {
+                *acc = Some(val);
+            }
+        } else if res.replace(val).is_some() {
+            // two values at the top level
+            return Err(Error::RPNSyntaxError());
+        }
+        Ok(())
+    }
+
+    fn close_frame(frame: Frame) -> Result<TruthTable> {
+        let (_, acc, neg) = frame;
+        let mut val = acc.ok_or(Error::EmptyPn())?;
+        if neg {
+            val.map_words(|w| !w);
+        }
+        Ok(val)
+    }
+
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut res = None;
+    for (e, sign) in pn.iter().rev() {
+        if !sign.is_empty() && sign != "~" {
+            return Err(Error::SignInBitwise(sign.clone()));
+        }
+
+        let bool_sign = sign == "~";
+
+        match e {
+            PnElem::Op(op) => stack.push((*op, None, bool_sign)),
+            PnElem::Close => {
+                let frame = stack.pop().ok_or(Error::RPNSyntaxError())?;
+                push_val(&mut stack, &mut res, close_frame(frame)?)?;
+            }
+            // csts must have been replaced by pseudo-variables
+            PnElem::Cst(cst) => return Err(Error::CstInBitwise(*cst)),
+            PnElem::Var(var) => {
+                let mut val = var_map
+                    .get(var)
+                    .ok_or(ArenaError::UnknownVar(*var))?
+                    .clone();
+                if bool_sign {
+                    val.map_words(|w| !w);
+                }
+                push_val(&mut stack, &mut res, val)?;
+            }
+        }
+    }
+
+    while let Some(frame) = stack.pop() {
+        push_val(&mut stack, &mut res, close_frame(frame)?)?;
     }
 
-    (res, num_var)
+    res.ok_or(Error::EmptyPn())
 }
 
-fn compute_bool(op: char, b1: bool, b2: bool) -> bool {
+// op is one of '^', '|' and '&'
+fn compute_bool(op: char, w1: u64, w2: u64) -> u64 {
     match op {
-        '^' => b1 ^ b2,
-        '|' => b1 | b2,
-        '&' => b1 & b2,
-        _ => unreachable!(),
+        '^' => w1 ^ w2,
+        '|' => w1 | w2,
+        _ => w1 & w2,
     }
 }
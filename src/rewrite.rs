@@ -0,0 +1,447 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::error::{Error, Result};
+use crate::expr::arena::{Arena, Elem};
+use crate::expr::factory::ArenaFactory;
+use crate::expr::node::VarTerm;
+use crate::expr::utils::{compute_op, match_elem, width_mask};
+use crate::parser::parse_rpn;
+
+// Ops whose children are matched in any order
+const COMMUTATIVE_OPS: &str = "+^&|";
+
+// Max num of rewrites of a call to rewrite, a rule set looping on itself
+// stops there
+pub const MAX_REWRITES: usize = 10000;
+
+// Pattern, written in the syntax of expressions: each variable stands for
+// any subterm, the same one at each of its occurrences.
+// Csts are read on 64 bits, and masked to the width of the arena.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Pat {
+    Var(String, String),
+    Node(char, String, Option<u64>, Vec<Pat>),
+}
+
+impl Pat {
+    fn parse(expr: &str) -> Result<Pat> {
+        let arena = ArenaFactory::new_arena_width(&mut parse_rpn(expr.to_string())?, 64)?;
+        Pat::from_arena(&arena, arena.root_node)
+    }
+
+    fn from_arena(arena: &Arena, idx: usize) -> Result<Pat> {
+        match_elem(
+            arena.get(idx)?,
+            |_| {
+                let (op, sign) = arena.copy_op_sign(idx)?;
+                let ch = arena
+                    .get_ch(idx)?
+                    .into_iter()
+                    .map(|ch_idx| Pat::from_arena(arena, ch_idx))
+                    .collect::<Result<Vec<Pat>>>()?;
+                Ok(Pat::Node(op, sign, arena.get_cst(idx)?, ch))
+            },
+            |l| {
+                let name = arena.var_name(l.val.val)?.to_string();
+                Ok(Pat::Var(name, l.val.sign.clone()))
+            },
+        )?
+    }
+
+    fn vars(&self) -> BTreeSet<&str> {
+        match self {
+            Pat::Var(name, _) => std::iter::once(name.as_str()).collect(),
+            Pat::Node(_, _, _, ch) => ch.iter().flat_map(Pat::vars).collect(),
+        }
+    }
+}
+
+// Binding of a pattern variable: the element at idx, with its sign replaced
+// by the given one
+type Binds = HashMap<String, (usize, String)>;
+
+// Op, children and cst of a node left out of a partial match
+type Rest = (char, Vec<usize>, Option<u64>);
+
+pub struct Rule {
+    name: String,
+    lhs_str: String,
+    rhs_str: String,
+    lhs: Pat,
+    rhs: Pat,
+}
+
+impl Rule {
+    // All variables of rhs must appear in lhs
+    pub fn new(name: &str, lhs: &str, rhs: &str) -> Result<Self> {
+        let (lhs_pat, rhs_pat) = (Pat::parse(lhs)?, Pat::parse(rhs)?);
+        let lhs_vars = lhs_pat.vars();
+        if let Some(v) = rhs_pat.vars().into_iter().find(|v| !lhs_vars.contains(v)) {
+            return Err(Error::UnboundPatternVar(v.to_string()));
+        }
+
+        Ok(Rule {
+            name: name.to_string(),
+            lhs_str: lhs.to_string(),
+            rhs_str: rhs.to_string(),
+            lhs: lhs_pat,
+            rhs: rhs_pat,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn lhs(&self) -> &str {
+        &self.lhs_str
+    }
+
+    pub fn rhs(&self) -> &str {
+        &self.rhs_str
+    }
+
+    pub(crate) fn lhs_pat(&self) -> &Pat {
+        &self.lhs
+    }
+
+    pub(crate) fn rhs_pat(&self) -> &Pat {
+        &self.rhs
+    }
+
+    // Rewrite the element at idx in place, if it matches lhs. An unsigned
+    // lhs node of a commutative op also matches part of the children of a
+    // node, the others being kept next to rhs: "(a^b)+2.(a&b)" matches
+    // "(x^y)+z+2.(x&y)".
+    pub fn apply(&self, arena: &mut Arena, idx: usize) -> Result<bool> {
+        let (binds, outer, rest) = match self.match_root(arena, idx)? {
+            Some(m) => m,
+            None => return Ok(false),
+        };
+
+        let rhs_idx = build(arena, &self.rhs, &binds)?;
+        let new_idx = match rest {
+            None => push_sign(arena, rhs_idx, &outer)?,
+            Some((op, mut ch, mut cst)) => {
+                merge(arena, op, rhs_idx, &mut ch, &mut cst)?;
+                let new_idx = arena.node_with(outer, op, cst, &ch)?;
+                if arena.get_pars(rhs_idx)?.is_empty() && rhs_idx != arena.root_node {
+                    arena.remove_subtree(rhs_idx)?;
+                }
+                new_idx
+            }
+        };
+
+        // hash-consing gives back idx if nothing changed
+        if new_idx == idx {
+            return Ok(false);
+        }
+        arena.replace(idx, new_idx)?;
+
+        Ok(true)
+    }
+
+    // Bindings, sign put back above rhs and, for a partial match, op,
+    // children and cst left out of the match
+    fn match_root(
+        &self,
+        arena: &Arena,
+        idx: usize,
+    ) -> Result<Option<(Binds, String, Option<Rest>)>> {
+        let (op, p_sign, p_cst, p_ch) = match &self.lhs {
+            Pat::Var(..) => {
+                let binds = match_pat(arena, &self.lhs, idx, &Binds::new())?;
+                return Ok(binds.into_iter().next().map(|b| (b, String::new(), None)));
+            }
+            Pat::Node(op, sign, cst, ch) => (*op, sign, *cst, ch),
+        };
+        if !matches!(arena.get(idx)?, Elem::Node(_)) {
+            return Ok(None);
+        }
+
+        let (t_op, t_sign) = arena.copy_op_sign(idx)?;
+        if t_op != op {
+            return Ok(None);
+        }
+        // an unsigned pattern matches under the sign of the node
+        let outer = if p_sign.is_empty() {
+            t_sign
+        } else if *p_sign == t_sign {
+            String::new()
+        } else {
+            return Ok(None);
+        };
+
+        let partial = p_sign.is_empty() && COMMUTATIVE_OPS.contains(op);
+        let t_cst = arena.get_cst(idx)?;
+        let rest_cst = match p_cst.map(|c| c & width_mask(arena.width())) {
+            Some(c) if t_cst == Some(c) => None,
+            Some(_) => return Ok(None),
+            None if partial || t_cst.is_none() => t_cst,
+            None => return Ok(None),
+        };
+
+        let t_ch = arena.get_ch(idx)?;
+        if t_ch.len() < p_ch.len() || (!partial && t_ch.len() != p_ch.len()) {
+            return Ok(None);
+        }
+
+        let (binds, used) = match match_ch(arena, op, p_ch, &t_ch, &Binds::new())?
+            .into_iter()
+            .next()
+        {
+            Some(sol) => sol,
+            None => return Ok(None),
+        };
+
+        let rest_ch: Vec<usize> = t_ch
+            .iter()
+            .enumerate()
+            .filter(|(pos, _)| !used.contains(pos))
+            .map(|(_, &ch_idx)| ch_idx)
+            .collect();
+        if rest_ch.is_empty() && rest_cst.is_none() {
+            return Ok(Some((binds, outer, None)));
+        }
+
+        Ok(Some((binds, outer, Some((op, rest_ch, rest_cst)))))
+    }
+}
+
+// Apply the rules to the expression at root_idx until none matches,
+// innermost nodes first. The root slot is kept.
+pub fn rewrite(arena: &mut Arena, root_idx: usize, rules: &[Rule]) -> Result<RewriteReport> {
+    let mut fired = Vec::new();
+    'rescan: while fired.len() < MAX_REWRITES {
+        // the preorder is stale after each rewrite
+        for idx in arena.get_preorder(root_idx, false)?.into_iter().rev() {
+            for rule in rules {
+                if rule.apply(arena, idx)? {
+                    fired.push(rule.name.clone());
+                    continue 'rescan;
+                }
+            }
+        }
+
+        return Ok(RewriteReport {
+            fired,
+            fixpoint: true,
+        });
+    }
+
+    Ok(RewriteReport {
+        fired,
+        fixpoint: false,
+    })
+}
+
+// Names of the rules, in the order they fired. No fixpoint means that
+// MAX_REWRITES was reached.
+#[derive(Debug, PartialEq)]
+pub struct RewriteReport {
+    pub fired: Vec<String>,
+    pub fixpoint: bool,
+}
+
+// Known MBA identities, the longest patterns first
+pub fn mba_rules() -> Result<Vec<Rule>> {
+    [
+        ("xor-and-add", "(a^b)+2.(a&b)", "a+b"),
+        ("or-or-xor-add", "2.(a|b)-(a^b)", "a+b"),
+        ("add-and-xor", "a+b-2.(a&b)", "a^b"),
+        ("add-and-or", "a+b-(a&b)", "a|b"),
+        ("add-or-and", "a+b-(a|b)", "a&b"),
+        ("or-and-add", "(a|b)+(a&b)", "a+b"),
+        ("or-and-xor", "(a|b)-(a&b)", "a^b"),
+        ("xor-and-or", "(a^b)+(a&b)", "a|b"),
+        ("or-xor-and", "(a|b)-(a^b)", "a&b"),
+        ("or-sub", "(a|b)-b", "a&~b"),
+        ("and-sub", "a-(a&b)", "a&~b"),
+        ("and-not-add", "(a&~b)+(a&b)", "a"),
+        ("add-sub", "a+b-b", "a"),
+    ]
+    .iter()
+    .map(|(name, lhs, rhs)| Rule::new(name, lhs, rhs))
+    .collect()
+}
+
+fn match_pat(arena: &Arena, pat: &Pat, idx: usize, binds: &Binds) -> Result<Vec<Binds>> {
+    match pat {
+        Pat::Var(name, p_sign) => {
+            // the sign of the pattern is the outermost part of the one of idx
+            let sign = arena.get_sign(idx)?;
+            let v_sign = match sign.strip_prefix(p_sign.as_str()) {
+                Some(v_sign) => v_sign.to_string(),
+                None => return Ok(vec![]),
+            };
+
+            match binds.get(name) {
+                Some((b_idx, b_sign)) => {
+                    if *b_sign == v_sign && same_core(arena, *b_idx, idx)? {
+                        Ok(vec![binds.clone()])
+                    } else {
+                        Ok(vec![])
+                    }
+                }
+                None => {
+                    let mut binds = binds.clone();
+                    binds.insert(name.clone(), (idx, v_sign));
+                    Ok(vec![binds])
+                }
+            }
+        }
+        Pat::Node(op, sign, cst, ch) => {
+            if !matches!(arena.get(idx)?, Elem::Node(_)) {
+                return Ok(vec![]);
+            }
+
+            let cst = cst.map(|c| c & width_mask(arena.width()));
+            let t_ch = arena.get_ch(idx)?;
+            if arena.copy_op_sign(idx)? != (*op, sign.clone())
+                || arena.get_cst(idx)? != cst
+                || t_ch.len() != ch.len()
+            {
+                return Ok(vec![]);
+            }
+
+            Ok(match_ch(arena, *op, ch, &t_ch, binds)?
+                .into_iter()
+                .map(|(binds, _)| binds)
+                .collect())
+        }
+    }
+}
+
+// All ways of matching each pattern with a distinct target, given with the
+// positions of the targets used. Targets of a non commutative op are
+// matched in order.
+fn match_ch(
+    arena: &Arena,
+    op: char,
+    pats: &[Pat],
+    targets: &[usize],
+    binds: &Binds,
+) -> Result<Vec<(Binds, Vec<usize>)>> {
+    let mut sols = vec![(binds.clone(), Vec::new())];
+    for (pos, pat) in pats.iter().enumerate() {
+        let mut next_sols = Vec::new();
+        for (binds, used) in sols {
+            let cand: Vec<usize> = if COMMUTATIVE_OPS.contains(op) {
+                (0..targets.len()).filter(|t| !used.contains(t)).collect()
+            } else {
+                vec![pos]
+            };
+
+            for t in cand {
+                for new_binds in match_pat(arena, pat, targets[t], &binds)? {
+                    let mut new_used = used.clone();
+                    new_used.push(t);
+                    next_sols.push((new_binds, new_used));
+                }
+            }
+        }
+        sols = next_sols;
+    }
+
+    Ok(sols)
+}
+
+// Same element, signs apart
+fn same_core(arena: &Arena, idx1: usize, idx2: usize) -> Result<bool> {
+    if idx1 == idx2 {
+        return Ok(true);
+    }
+
+    match (arena.get(idx1)?, arena.get(idx2)?) {
+        (Elem::Leaf(l1), Elem::Leaf(l2)) => Ok(l1.val.val == l2.val.val),
+        (Elem::Node(_), Elem::Node(_)) => Ok(arena.copy_op_sign(idx1)?.0
+            == arena.copy_op_sign(idx2)?.0
+            && arena.get_cst(idx1)? == arena.get_cst(idx2)?
+            && arena.get_ch(idx1)? == arena.get_ch(idx2)?),
+        _ => Ok(false),
+    }
+}
+
+// Insert the pattern, its variables replaced by their bindings
+fn build(arena: &mut Arena, pat: &Pat, binds: &Binds) -> Result<usize> {
+    match pat {
+        Pat::Var(name, p_sign) => {
+            let (idx, v_sign) = binds
+                .get(name)
+                .ok_or_else(|| Error::UnboundPatternVar(name.clone()))?;
+            with_sign(arena, *idx, cancel_sign(&format!("{}{}", p_sign, v_sign)))
+        }
+        Pat::Node(op, sign, cst, ch) => {
+            let (mut ch_idx, mut cst) = (Vec::new(), *cst);
+            for ch_pat in ch {
+                let idx = build(arena, ch_pat, binds)?;
+                merge(arena, *op, idx, &mut ch_idx, &mut cst)?;
+            }
+            Ok(arena.node_with(sign.clone(), *op, cst, &ch_idx)?)
+        }
+    }
+}
+
+// Add idx to the terms of a node of op, an unsigned node of the same op
+// being flattened into them
+fn merge(
+    arena: &Arena,
+    op: char,
+    idx: usize,
+    ch: &mut Vec<usize>,
+    cst: &mut Option<u64>,
+) -> Result<()> {
+    let flat = matches!(arena.get(idx)?, Elem::Node(_))
+        && COMMUTATIVE_OPS.contains(op)
+        && arena.copy_op_sign(idx)? == (op, String::new());
+    if !flat {
+        ch.push(idx);
+        return Ok(());
+    }
+
+    ch.append(&mut arena.get_ch(idx)?);
+    if let Some(c) = arena.get_cst(idx)? {
+        let width = arena.width();
+        *cst = Some(cst.map_or(c, |old| compute_op(op, old, c, width)));
+    }
+
+    Ok(())
+}
+
+// The element at idx, with sign as its own sign
+pub(crate) fn with_sign(arena: &mut Arena, idx: usize, sign: String) -> Result<usize> {
+    if arena.get_sign(idx)? == sign {
+        return Ok(idx);
+    }
+
+    match arena.get(idx)? {
+        Elem::Leaf(l) => {
+            let val = l.val.val;
+            Ok(arena.leaf(VarTerm { val, sign })?)
+        }
+        _ => {
+            let (op, _) = arena.copy_op_sign(idx)?;
+            let (cst, ch) = (arena.get_cst(idx)?, arena.get_ch(idx)?);
+            Ok(arena.node_with(sign, op, cst, &ch)?)
+        }
+    }
+}
+
+// The element at idx, under sign
+fn push_sign(arena: &mut Arena, idx: usize, sign: &str) -> Result<usize> {
+    let new_sign = cancel_sign(&format!("{}{}", sign, arena.get_sign(idx)?));
+    with_sign(arena, idx, new_sign)
+}
+
+// "~~" and "--" cancel out
+pub(crate) fn cancel_sign(sign: &str) -> String {
+    let mut res = String::new();
+    for s in sign.chars() {
+        if res.ends_with(s) {
+            res.pop();
+        } else {
+            res.push(s);
+        }
+    }
+    res
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use intbits::Bits;
+
+use crate::error::{ArenaError, Result};
+use crate::expr::arena::Arena;
+use crate::expr::node::VarTerm;
+use crate::reduce::TruthTable;
+
+mod table3;
+
+// Bitwise expression over variable positions, as synthesized from a truth
+// table, before being inserted into an arena
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BoolExpr {
+    Var(u8),
+    Not(Box<BoolExpr>),
+    Op(char, Box<BoolExpr>, Box<BoolExpr>),
+}
+
+impl BoolExpr {
+    pub fn negate(e: BoolExpr) -> BoolExpr {
+        match e {
+            BoolExpr::Not(e) => *e,
+            e => BoolExpr::Not(Box::new(e)),
+        }
+    }
+
+    pub fn op(op: char, e1: BoolExpr, e2: BoolExpr) -> BoolExpr {
+        BoolExpr::Op(op, Box::new(e1), Box::new(e2))
+    }
+
+    // Conjunction of the variables whose position is set in i, None if i is 0
+    pub fn conj(i: usize) -> Option<BoolExpr> {
+        (0..usize::N_BITS)
+            .filter(|&pos| i.bit(pos))
+            .map(|pos| BoolExpr::Var(pos as u8))
+            .reduce(|acc, e| BoolExpr::op('&', acc, e))
+    }
+
+    // Number of operators, ~ included
+    pub fn cost(&self) -> usize {
+        match self {
+            BoolExpr::Var(_) => 0,
+            BoolExpr::Not(e) => e.cost() + 1,
+            BoolExpr::Op(_, e1, e2) => e1.cost() + e2.cost() + 1,
+        }
+    }
+
+    // Longest path of operators, ~ included
+    pub fn depth(&self) -> usize {
+        match self {
+            BoolExpr::Var(_) => 0,
+            BoolExpr::Not(e) => e.depth() + 1,
+            BoolExpr::Op(_, e1, e2) => e1.depth().max(e2.depth()) + 1,
+        }
+    }
+
+    // Postfix form, positions 0, 1, 2... being the letters a, b, c...
+    pub fn to_postfix(&self) -> String {
+        match self {
+            BoolExpr::Var(pos) => ((b'a' + pos) as char).to_string(),
+            BoolExpr::Not(e) => format!("{}~", e.to_postfix()),
+            BoolExpr::Op(op, e1, e2) => format!("{}{}{}", e1.to_postfix(), e2.to_postfix(), op),
+        }
+    }
+
+    pub fn from_postfix(s: &str) -> Option<BoolExpr> {
+        let mut stack = Vec::new();
+        for c in s.chars() {
+            match c {
+                'a'..='z' => stack.push(BoolExpr::Var(c as u8 - b'a')),
+                '~' => {
+                    let e = stack.pop()?;
+                    stack.push(BoolExpr::negate(e));
+                }
+                '&' | '|' | '^' => {
+                    let e2 = stack.pop()?;
+                    let e1 = stack.pop()?;
+                    stack.push(BoolExpr::op(c, e1, e2));
+                }
+                _ => return None,
+            }
+        }
+
+        match (stack.pop(), stack.is_empty()) {
+            (Some(e), true) => Some(e),
+            _ => None,
+        }
+    }
+
+    pub fn truth_table(&self, num_vars: u8) -> Result<TruthTable> {
+        match self {
+            BoolExpr::Var(pos) => TruthTable::var(num_vars, *pos),
+            BoolExpr::Not(e) => Ok(!&e.truth_table(num_vars)?),
+            BoolExpr::Op(op, e1, e2) => {
+                let tt1 = e1.truth_table(num_vars)?;
+                let tt2 = e2.truth_table(num_vars)?;
+                match op {
+                    '&' => Ok(&tt1 & &tt2),
+                    '|' => Ok(&tt1 | &tt2),
+                    '^' => Ok(&tt1 ^ &tt2),
+                    _ => Err(ArenaError::UnknownOp(*op).into()),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn map_vars(&self, pos_map: &[u8]) -> BoolExpr {
+        match self {
+            BoolExpr::Var(pos) => BoolExpr::Var(pos_map[*pos as usize]),
+            BoolExpr::Not(e) => BoolExpr::negate(e.map_vars(pos_map)),
+            BoolExpr::Op(op, e1, e2) => {
+                BoolExpr::op(*op, e1.map_vars(pos_map), e2.map_vars(pos_map))
+            }
+        }
+    }
+
+    // Insert the expression in the arena, vars[pos] being the variable of
+    // position pos. Returns the index of the new subtree.
+    pub fn insert(&self, arena: &mut Arena, vars: &[usize]) -> Result<usize> {
+        self.insert_sign(arena, vars, String::new())
+    }
+
+    pub fn insert_sign(
+        &self,
+        arena: &mut Arena,
+        vars: &[usize],
+        mut sign: String,
+    ) -> Result<usize> {
+        match self {
+            BoolExpr::Var(pos) => {
+                let val = *vars
+                    .get(*pos as usize)
+                    .ok_or(ArenaError::UnknownVar(*pos as usize))?;
+                Ok(arena.leaf(VarTerm { val, sign })?)
+            }
+            BoolExpr::Not(e) => {
+                sign.push('~');
+                e.insert_sign(arena, vars, sign)
+            }
+            BoolExpr::Op(op, _, _) => {
+                let ch = self
+                    .operands(*op)
+                    .iter()
+                    .rev()
+                    .map(|e| e.insert(arena, vars))
+                    .collect::<Result<Vec<usize>>>()?;
+                Ok(arena.node_with(sign, *op, None, &ch)?)
+            }
+        }
+    }
+
+    // operands of nested op of the same kind are merged, as in the arena
+    pub(crate) fn operands(&self, op: char) -> Vec<&BoolExpr> {
+        match self {
+            BoolExpr::Op(e_op, e1, e2) if *e_op == op => {
+                let mut res = e1.operands(op);
+                res.append(&mut e2.operands(op));
+                res
+            }
+            e => vec![e],
+        }
+    }
+}
+
+// Metric minimized by the table of 3 variables functions, the other one
+// breaking ties
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cost {
+    Nodes,
+    Depth,
+}
+
+impl Cost {
+    fn key(self, e: &BoolExpr) -> (usize, usize) {
+        match self {
+            Cost::Nodes => (e.cost(), e.depth()),
+            Cost::Depth => (e.depth(), e.cost()),
+        }
+    }
+
+    // Cost of an op over operands of costs c1 and c2
+    fn combine(self, c1: usize, c2: usize) -> usize {
+        match self {
+            Cost::Nodes => c1 + c2 + 1,
+            Cost::Depth => c1.max(c2) + 1,
+        }
+    }
+}
+
+// Cheapest expressions for all functions of 3 variables, indexed by their
+// 8-rows truth table. Expressions are enumerated by increasing cost, each
+// level built from the cheaper ones. Constant functions have no expression.
+pub fn generate_table3(cost: Cost) -> Vec<Option<BoolExpr>> {
+    let is_const = |f: usize| f == 0 || f == 0xff;
+    let mut best: Vec<Option<BoolExpr>> = vec![None; 256];
+    // truth tables of x0, x1 and x2
+    let mut levels: Vec<Vec<usize>> = vec![vec![0xaa, 0xcc, 0xf0]];
+    for (pos, &f) in levels[0].iter().enumerate() {
+        best[f] = Some(BoolExpr::Var(pos as u8));
+    }
+
+    let mut num_found = levels[0].len();
+    while num_found < 254 {
+        let c = levels.len();
+        let mut cand: Vec<Option<BoolExpr>> = vec![None; 256];
+        let mut offer = |best: &[Option<BoolExpr>], f: usize, e: BoolExpr| {
+            if is_const(f) || best[f].is_some() {
+                return;
+            }
+            if cand[f].as_ref().is_none_or(|b| cost.key(&e) < cost.key(b)) {
+                cand[f] = Some(e);
+            }
+        };
+
+        for &f in levels[c - 1].iter() {
+            if let Some(e) = &best[f] {
+                offer(&best, !f & 0xff, BoolExpr::negate(e.clone()));
+            }
+        }
+        for i in 0..c {
+            for j in (i..c).filter(|&j| cost.combine(i, j) == c) {
+                for &f1 in levels[i].iter() {
+                    for &f2 in levels[j].iter() {
+                        if let (Some(e1), Some(e2)) = (&best[f1], &best[f2]) {
+                            for (op, f) in [('&', f1 & f2), ('|', f1 | f2), ('^', f1 ^ f2)] {
+                                offer(&best, f, BoolExpr::op(op, e1.clone(), e2.clone()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let level: Vec<usize> = (0..256).filter(|&f| cand[f].is_some()).collect();
+        for &f in level.iter() {
+            best[f] = cand[f].take();
+        }
+        num_found += level.len();
+        levels.push(level);
+    }
+
+    best
+}
+
+// Rust source of the checked-in table, for both costs
+pub fn table3_source() -> String {
+    let entries = |cost: Cost| -> String {
+        generate_table3(cost)
+            .iter()
+            .map(|e| {
+                format!(
+                    "    \"{}\",\n",
+                    e.as_ref().map_or(String::new(), |e| e.to_postfix())
+                )
+            })
+            .collect()
+    };
+
+    format!(
+        "// Generated by synth::table3_source, do not edit: cheapest expressions\n\
+         // of the functions of 3 variables, indexed by their truth table, in\n\
+         // postfix form. Constant functions are empty.\n\n\
+         pub(super) static NODES: [&str; 256] = [\n{}];\n\n\
+         pub(super) static DEPTH: [&str; 256] = [\n{}];\n",
+        entries(Cost::Nodes),
+        entries(Cost::Depth)
+    )
+}
+
+// Cheapest expression of the function of 3 variables whose 8-rows truth
+// table is tt, from the checked-in table
+pub fn lookup3(tt: u8, cost: Cost) -> Option<BoolExpr> {
+    table3(cost)[tt as usize].clone()
+}
+
+fn table3(cost: Cost) -> &'static Vec<Option<BoolExpr>> {
+    static NODES: OnceLock<Vec<Option<BoolExpr>>> = OnceLock::new();
+    static DEPTH: OnceLock<Vec<Option<BoolExpr>>> = OnceLock::new();
+    let (lock, src) = match cost {
+        Cost::Nodes => (&NODES, &table3::NODES),
+        Cost::Depth => (&DEPTH, &table3::DEPTH),
+    };
+    lock.get_or_init(|| src.iter().map(|s| BoolExpr::from_postfix(s)).collect())
+}
+
+// Cheapest expression found over & | ^ ~ for the truth table: exact for the
+// given cost up to 3 (essential) variables. Above, a Shannon decomposition
+// is built regardless of the cost, only its 3 variables cofactors being
+// looked up for it.
+// Constant functions are not bitwise expressions, None is returned.
+pub fn synthesize(tt: &TruthTable, cost: Cost) -> Option<BoolExpr> {
+    let pos_map: Vec<u8> = (0..tt.num_vars()).collect();
+    Synth::new(cost).synth(tt, &pos_map)
+}
+
+struct Synth {
+    cost: Cost,
+    memo: HashMap<TruthTable, Option<BoolExpr>>,
+}
+
+impl Synth {
+    fn new(cost: Cost) -> Self {
+        Synth {
+            cost,
+            memo: HashMap::new(),
+        }
+    }
+
+    // pos_map gives, for each var of tt, its position in the original table
+    fn synth(&mut self, tt: &TruthTable, pos_map: &[u8]) -> Option<BoolExpr> {
+        if tt.is_const().is_some() {
+            return None;
+        }
+
+        // drop variables the function does not depend on
+        let essential: Vec<u8> = (0..tt.num_vars()).filter(|&p| tt.depends_on(p)).collect();
+        if essential.len() < tt.num_vars() as usize {
+            let mut aux_tt = tt.clone();
+            for pos in (0..tt.num_vars()).rev() {
+                if !essential.contains(&pos) {
+                    aux_tt = aux_tt.cofactor(pos, false);
+                }
+            }
+            let aux_map: Vec<u8> = essential.iter().map(|&p| pos_map[p as usize]).collect();
+            return self.synth(&aux_tt, &aux_map);
+        }
+
+        if tt.num_vars() <= 3 {
+            let mut tt3 = tt.words()[0];
+            for exp in tt.num_vars()..3 {
+                tt3 |= tt3 << (1 << exp);
+            }
+            return table3(self.cost)[tt3 as usize & 0xff]
+                .as_ref()
+                .map(|e| e.map_vars(pos_map));
+        }
+
+        if let Some(e) = self.memo.get(tt) {
+            return e.as_ref().map(|e| e.map_vars(pos_map));
+        }
+
+        let local_map: Vec<u8> = (0..tt.num_vars()).collect();
+        let res = self.shannon(tt, &local_map);
+        self.memo.insert(tt.clone(), res.clone());
+
+        res.map(|e| e.map_vars(pos_map))
+    }
+
+    // Shannon decomposition f = v ? f1 : f0, on the variable giving the
+    // simplest cofactors
+    fn shannon(&mut self, tt: &TruthTable, pos_map: &[u8]) -> Option<BoolExpr> {
+        let cofactors = |pos: u8| (tt.cofactor(pos, false), tt.cofactor(pos, true));
+        let is_simple = |f0: &TruthTable, f1: &TruthTable| {
+            f0.is_const().is_some() || f1.is_const().is_some() || *f1 == !f0
+        };
+
+        let pos = (0..tt.num_vars())
+            .rev()
+            .find(|&p| {
+                let (f0, f1) = cofactors(p);
+                is_simple(&f0, &f1)
+            })
+            .unwrap_or(tt.num_vars() - 1);
+        let (f0, f1) = cofactors(pos);
+
+        let sub_map: Vec<u8> = pos_map
+            .iter()
+            .enumerate()
+            .filter(|&(p, _)| p != pos as usize)
+            .map(|(_, v)| *v)
+            .collect();
+        let v = BoolExpr::Var(pos_map[pos as usize]);
+
+        let res = match (f0.is_const(), f1.is_const()) {
+            (Some(false), Some(true)) => v,
+            (Some(true), Some(false)) => BoolExpr::negate(v),
+            (Some(false), None) => BoolExpr::op('&', v, self.synth(&f1, &sub_map)?),
+            (Some(true), None) => {
+                BoolExpr::op('|', BoolExpr::negate(v), self.synth(&f1, &sub_map)?)
+            }
+            (None, Some(false)) => {
+                BoolExpr::op('&', BoolExpr::negate(v), self.synth(&f0, &sub_map)?)
+            }
+            (None, Some(true)) => BoolExpr::op('|', v, self.synth(&f0, &sub_map)?),
+            _ if f1 == !&f0 => BoolExpr::op('^', v, self.synth(&f0, &sub_map)?),
+            // f0 implies f1: f = f0 | (v & f1)
+            _ if (&f0 & &f1) == f0 => BoolExpr::op(
+                '|',
+                self.synth(&f0, &sub_map)?,
+                BoolExpr::op('&', v, self.synth(&f1, &sub_map)?),
+            ),
+            // f1 implies f0: f = f1 | (~v & f0)
+            _ if (&f0 & &f1) == f1 => BoolExpr::op(
+                '|',
+                self.synth(&f1, &sub_map)?,
+                BoolExpr::op('&', BoolExpr::negate(v), self.synth(&f0, &sub_map)?),
+            ),
+            _ => {
+                let e0 = self.synth(&f0, &sub_map)?;
+                let e1 = self.synth(&f1, &sub_map)?;
+                BoolExpr::op(
+                    '|',
+                    BoolExpr::op('&', v.clone(), e1),
+                    BoolExpr::op('&', BoolExpr::negate(v), e0),
+                )
+            }
+        };
+
+        Some(res)
+    }
+}
@@ -0,0 +1,521 @@
+// Generated by synth::table3_source, do not edit: cheapest expressions
+// of the functions of 3 variables, indexed by their truth table, in
+// postfix form. Constant functions are empty.
+
+pub(super) static NODES: [&str; 256] = [
+    "",
+    "abc||~",
+    "abc|~&",
+    "bc|~",
+    "bac|~&",
+    "ac|~",
+    "c~ab^&",
+    "cab&|~",
+    "c~ab&&",
+    "cab^|~",
+    "ac~&",
+    "cb~ac||^",
+    "bc~&",
+    "ca~bc||^",
+    "c~ab|&",
+    "c~",
+    "cab|~&",
+    "ab|~",
+    "b~ac^&",
+    "bac&|~",
+    "bc^a~&",
+    "abc&|~",
+    "abc^ab&|^",
+    "c~bc^ac^&^",
+    "bc^ac^&",
+    "ab^ac&|~",
+    "cabc&|^",
+    "b~abc^&^",
+    "cbac&|^",
+    "a~bac^&^",
+    "cab|^",
+    "cab|&~",
+    "b~ac&&",
+    "bac^|~",
+    "ab~&",
+    "bc~ab||^",
+    "bc^ab^&",
+    "ac^ab&|~",
+    "babc&|^",
+    "c~abc^&^",
+    "abc^&",
+    "bc|a~bc&|^",
+    "abc&~&",
+    "c~bc^ab^&^",
+    "bc^ab|&",
+    "cba~|^",
+    "bc&ab|^",
+    "c~ab~&|",
+    "bbc|^",
+    "ba~bc||^",
+    "b~ac|&",
+    "b~",
+    "bcab&|^",
+    "a~cab^&^",
+    "bac|^",
+    "bac|&~",
+    "bc^ac|&",
+    "bca~|^",
+    "bc&ac|^",
+    "b~ac~&|",
+    "bc^",
+    "bc^ac|~|",
+    "bc^ac~&|",
+    "bc&~",
+    "a~bc&&",
+    "abc^|~",
+    "ac^ab^&",
+    "bc^ab&|~",
+    "aab|^",
+    "ac~ab||^",
+    "abac&|^",
+    "c~bac^&^",
+    "bac^&",
+    "ac|b~ac&|^",
+    "ac^ab|&",
+    "cab~|^",
+    "bac&~&",
+    "c~ac^ab^&^",
+    "ac&ab|^",
+    "c~aab|^|",
+    "aac|^",
+    "ab~ac||^",
+    "acab&|^",
+    "b~cab^&^",
+    "a~bc|&",
+    "a~",
+    "abc|^",
+    "abc|&~",
+    "ac^bc|&",
+    "acb~|^",
+    "ac^",
+    "ac^bc|~|",
+    "ac&bc|^",
+    "a~bc~&|",
+    "ac^bc~&|",
+    "ac&~",
+    "cab^&",
+    "ab|c~ab&|^",
+    "ab^ac|&",
+    "bac~|^",
+    "ab^bc|&",
+    "abc~|^",
+    "ab^",
+    "ab^bc|~|",
+    "ab&cab|&^",
+    "c~ab^^",
+    "abc&^",
+    "c~ab^bc|&^",
+    "bac&^",
+    "c~ab^ac|&^",
+    "ab^ac~&|",
+    "c~ab^|",
+    "cab&~&",
+    "c~bc^ac^|^",
+    "ab&ac|^",
+    "b~aac|^|",
+    "ab&bc|^",
+    "a~bbc|^|",
+    "ab^bbc|^|",
+    "ab&~",
+    "cab&^",
+    "b~ac^ab|&^",
+    "ac^ab~&|",
+    "b~ac^|",
+    "bc^aab|^|",
+    "bc^a~|",
+    "bc^ac^|",
+    "abc&&~",
+    "abc&&",
+    "bc^ac^|~",
+    "aabc^&^",
+    "bc|~abc&&|",
+    "bbac^&^",
+    "ac|~abc&&|",
+    "abc^ab|&^",
+    "c~ab&^",
+    "ab&",
+    "ab^c~ab||^",
+    "abc~|&",
+    "ab&bc|^~",
+    "bac~|&",
+    "ab&ac|^~",
+    "abc^ab^&^",
+    "c~ab&|",
+    "ccab^&^",
+    "ab|~abc&&|",
+    "abc^ac|&^",
+    "b~ac&^",
+    "bac^bc|&^",
+    "a~bc&^",
+    "abc^^",
+    "bc|~abc^^|",
+    "ab~ac|&^",
+    "ab^~",
+    "ac^bc&^",
+    "ab^bc|&~",
+    "bc^ac&^",
+    "ab^ac|&~",
+    "ab^cab&|^",
+    "cab^&~",
+    "ac&",
+    "ac^b~ac||^",
+    "acb~|&",
+    "ac&bc|^~",
+    "ac~ab|&^",
+    "ac^~",
+    "ac^bc|^",
+    "ac^bc|&~",
+    "abc|&",
+    "a~bc|^",
+    "a",
+    "abc|~|",
+    "bcab^&^",
+    "a~cab&|^",
+    "abc~&|",
+    "ac~|",
+    "cab~|&",
+    "ac&ab|^~",
+    "abc^ac^&^",
+    "b~ac&|",
+    "bc^ab&^",
+    "ac^ab|&~",
+    "ac^bac&|^",
+    "bac^&~",
+    "cbac^&^",
+    "a~bac&|^",
+    "abbc|^|",
+    "ab~|",
+    "bc^ab&|",
+    "ac^ab^&~",
+    "abc^|",
+    "abc&~|",
+    "bc&",
+    "bc^a~bc||^",
+    "bc~ab|&^",
+    "bc^~",
+    "bca~|&",
+    "bc&ac|^~",
+    "bc^ac|^",
+    "bc^ac|&~",
+    "bac|&",
+    "b~ac|^",
+    "acab^&^",
+    "b~cab&|^",
+    "b",
+    "bac|~|",
+    "bac~&|",
+    "bc~|",
+    "cba~|&",
+    "bc&ab|^~",
+    "bc^ab|^",
+    "bc^ab|&~",
+    "abc^ac^|^",
+    "a~bc&|",
+    "bc^abc&|^",
+    "abc^&~",
+    "cabc^&^",
+    "b~abc&|^",
+    "ac^ab&|",
+    "bc^ab^&~",
+    "baac|^|",
+    "ba~|",
+    "bac^|",
+    "bac&~|",
+    "cab|&",
+    "c~ab|^",
+    "abac^&^",
+    "c~bac&|^",
+    "babc^&^",
+    "c~abc&|^",
+    "ab^ac&|",
+    "bc^ac^&~",
+    "aac^ab^&^",
+    "c~ab^ac&|^",
+    "abc&|",
+    "abc^~|",
+    "bac&|",
+    "bac^~|",
+    "ab|",
+    "c~ab||",
+    "c",
+    "cab|~|",
+    "cab~&|",
+    "cb~|",
+    "caab|^|",
+    "ca~|",
+    "cab^|",
+    "cab&~|",
+    "cab&|",
+    "cab^~|",
+    "ac|",
+    "b~ac||",
+    "bc|",
+    "a~bc||",
+    "abc||",
+    "",
+];
+
+pub(super) static DEPTH: [&str; 256] = [
+    "",
+    "abc||~",
+    "abc|~&",
+    "bc|~",
+    "bac|~&",
+    "ac|~",
+    "c~ab^&",
+    "cab&|~",
+    "c~ab&&",
+    "cab^|~",
+    "ac~&",
+    "cb~ac||^",
+    "bc~&",
+    "ca~bc||^",
+    "c~ab|&",
+    "c~",
+    "cab|~&",
+    "ab|~",
+    "b~ac^&",
+    "bac&|~",
+    "bc^a~&",
+    "abc&|~",
+    "abc^ab&|^",
+    "c~bc^ac^&^",
+    "bc^ac^&",
+    "ab^ac&|~",
+    "cabc&|^",
+    "b~abc^&^",
+    "cbac&|^",
+    "a~bac^&^",
+    "cab|^",
+    "cab|&~",
+    "b~ac&&",
+    "bac^|~",
+    "ab~&",
+    "bc~ab||^",
+    "bc^ab^&",
+    "ac^ab&|~",
+    "babc&|^",
+    "c~abc^&^",
+    "abc^&",
+    "bc|a~bc&|^",
+    "abc&~&",
+    "c~bc^ab^&^",
+    "bc^ab|&",
+    "cba~|^",
+    "bc&ab|^",
+    "c~ab~&|",
+    "bbc|^",
+    "ba~bc||^",
+    "b~ac|&",
+    "b~",
+    "bcab&|^",
+    "a~cab^&^",
+    "bac|^",
+    "bac|&~",
+    "bc^ac|&",
+    "bca~|^",
+    "bc&ac|^",
+    "b~ac~&|",
+    "bc^",
+    "bc^ac|~|",
+    "bc^ac~&|",
+    "bc&~",
+    "a~bc&&",
+    "abc^|~",
+    "ac^ab^&",
+    "bc^ab&|~",
+    "aab|^",
+    "ac~ab||^",
+    "abac&|^",
+    "c~bac^&^",
+    "bac^&",
+    "ac|b~ac&|^",
+    "ac^ab|&",
+    "cab~|^",
+    "bac&~&",
+    "c~ac^ab^&^",
+    "ac&ab|^",
+    "c~aab|^|",
+    "aac|^",
+    "ab~ac||^",
+    "acab&|^",
+    "b~cab^&^",
+    "a~bc|&",
+    "a~",
+    "abc|^",
+    "abc|&~",
+    "ac^bc|&",
+    "acb~|^",
+    "ac^",
+    "ac^bc|~|",
+    "ac&bc|^",
+    "a~bc~&|",
+    "ac^bc~&|",
+    "ac&~",
+    "cab^&",
+    "ab|c~ab&|^",
+    "ab^ac|&",
+    "bac~|^",
+    "ab^bc|&",
+    "abc~|^",
+    "ab^",
+    "ab^bc|~|",
+    "ab&cab|&^",
+    "c~ab^^",
+    "abc&^",
+    "c~ab^bc|&^",
+    "bac&^",
+    "c~ab^ac|&^",
+    "ab^ac~&|",
+    "c~ab^|",
+    "cab&~&",
+    "c~bc^ac^|^",
+    "ab&ac|^",
+    "b~aac|^|",
+    "ab&bc|^",
+    "a~bbc|^|",
+    "ab^bbc|^|",
+    "ab&~",
+    "cab&^",
+    "b~ac^ab|&^",
+    "ac^ab~&|",
+    "b~ac^|",
+    "bc^aab|^|",
+    "bc^a~|",
+    "bc^ac^|",
+    "abc&&~",
+    "abc&&",
+    "bc^ac^|~",
+    "aabc^&^",
+    "bc|~abc&&|",
+    "bbac^&^",
+    "ac|~abc&&|",
+    "abc^ab|&^",
+    "c~ab&^",
+    "ab&",
+    "ab^c~ab||^",
+    "abc~|&",
+    "ab&bc|^~",
+    "bac~|&",
+    "ab&ac|^~",
+    "abc^ab^&^",
+    "c~ab&|",
+    "ccab^&^",
+    "ab|~abc&&|",
+    "abc^ac|&^",
+    "b~ac&^",
+    "bac^bc|&^",
+    "a~bc&^",
+    "abc^^",
+    "bc|~abc^^|",
+    "ab~ac|&^",
+    "ab^~",
+    "ac^bc&^",
+    "ab^bc|&~",
+    "bc^ac&^",
+    "ab^ac|&~",
+    "ab^cab&|^",
+    "cab^&~",
+    "ac&",
+    "ac^b~ac||^",
+    "acb~|&",
+    "ac&bc|^~",
+    "ac~ab|&^",
+    "ac^~",
+    "ac^bc|^",
+    "ac^bc|&~",
+    "abc|&",
+    "a~bc|^",
+    "a",
+    "abc|~|",
+    "bcab^&^",
+    "a~cab&|^",
+    "abc~&|",
+    "ac~|",
+    "cab~|&",
+    "ac&ab|^~",
+    "abc^ac^&^",
+    "b~ac&|",
+    "bc^ab&^",
+    "ac^ab|&~",
+    "ac^bac&|^",
+    "bac^&~",
+    "cbac^&^",
+    "a~bac&|^",
+    "abbc|^|",
+    "ab~|",
+    "bc^ab&|",
+    "ac^ab^&~",
+    "abc^|",
+    "abc&~|",
+    "bc&",
+    "bc^a~bc||^",
+    "bc~ab|&^",
+    "bc^~",
+    "bca~|&",
+    "bc&ac|^~",
+    "bc^ac|^",
+    "bc^ac|&~",
+    "bac|&",
+    "b~ac|^",
+    "acab^&^",
+    "b~cab&|^",
+    "b",
+    "bac|~|",
+    "bac~&|",
+    "bc~|",
+    "cba~|&",
+    "bc&ab|^~",
+    "bc^ab|^",
+    "bc^ab|&~",
+    "abc^ac^|^",
+    "a~bc&|",
+    "bc^abc&|^",
+    "abc^&~",
+    "cabc^&^",
+    "b~abc&|^",
+    "ac^ab&|",
+    "bc^ab^&~",
+    "baac|^|",
+    "ba~|",
+    "bac^|",
+    "bac&~|",
+    "cab|&",
+    "c~ab|^",
+    "abac^&^",
+    "c~bac&|^",
+    "babc^&^",
+    "c~abc&|^",
+    "ab^ac&|",
+    "bc^ac^&~",
+    "aac^ab^&^",
+    "c~ab^ac&|^",
+    "abc&|",
+    "abc^~|",
+    "bac&|",
+    "bac^~|",
+    "ab|",
+    "c~ab||",
+    "c",
+    "cab|~|",
+    "cab~&|",
+    "cb~|",
+    "caab|^|",
+    "ca~|",
+    "cab^|",
+    "cab&~|",
+    "cab&|",
+    "cab^~|",
+    "ac|",
+    "b~ac||",
+    "bc|",
+    "a~bc||",
+    "abc||",
+    "",
+];
@@ -1,14 +1,20 @@
-use dobf::error::ExprError;
+use std::collections::VecDeque;
+
+use dobf::error::{ArenaError, Error};
+use dobf::expr::arena::Arena;
 use dobf::expr::factory::ArenaFactory;
+use dobf::expr::node::VarTerm;
+use dobf::lexer::Token;
 use dobf::parser::parse_rpn;
+use dobf::reduce::simplify;
 
-fn get_expr_str(expr: String) -> Result<String, ExprError> {
+fn get_expr_str(expr: String) -> Result<String, Error> {
     let arena = ArenaFactory::new_arena(&mut parse_rpn(expr).unwrap())?;
-    Ok(format!("{}", arena.print()))
+    Ok(arena.print()?)
 }
 
 #[test]
-fn test_ok_simple() -> Result<(), ExprError> {
+fn test_ok_simple() -> Result<(), Error> {
     let res = get_expr_str("x".to_string())?;
     assert_eq!(res, "x".to_string());
 
@@ -49,7 +55,7 @@ fn test_ok_simple() -> Result<(), ExprError> {
 }
 
 #[test]
-fn test_ok_neg() -> Result<(), ExprError> {
+fn test_ok_neg() -> Result<(), Error> {
     let res = get_expr_str("x-y".to_string())?;
     assert_eq!(res, "+x-y/".to_string());
 
@@ -65,11 +71,20 @@ fn test_ok_neg() -> Result<(), ExprError> {
     let res = get_expr_str("x-~y+z".to_string())?;
     assert_eq!(res, "+x-~yz/".to_string());
 
+    let res = get_expr_str("x+-~-y".to_string())?;
+    assert_eq!(res, "+x-~-y/".to_string());
+
+    let res = get_expr_str("a-b-c".to_string())?;
+    assert_eq!(res, "+a-b-c/".to_string());
+
+    let res = get_expr_str("~x&y".to_string())?;
+    assert_eq!(res, "&~xy/".to_string());
+
     Ok(())
 }
 
 #[test]
-fn test_ok_complex() -> Result<(), ExprError> {
+fn test_ok_complex() -> Result<(), Error> {
     let res = get_expr_str("(t+a)^123.a^(x+y)^(c+y)".to_string())?;
     assert_eq!(res, "^+ta/.123a/+xy/+cy//".to_string());
 
@@ -77,7 +92,7 @@ fn test_ok_complex() -> Result<(), ExprError> {
 }
 
 #[test]
-fn test_from_ok_parser() -> Result<(), ExprError> {
+fn test_from_ok_parser() -> Result<(), Error> {
     let res = get_expr_str("y+((((x+y)^z)))".to_string())?;
     assert_eq!(res, "+y^+xy/z//".to_string());
 
@@ -93,7 +108,245 @@ fn test_from_ok_parser() -> Result<(), ExprError> {
     let res = get_expr_str("8458.(y&t&z) ^( x|y&z)&((x&y )& y|t) + x+ 9.(x|y)&y|z".to_string())?;
     assert_eq!(
         res,
-        "+.8458&ytz//^|xy/&z|&xyy/t///x|&.9|xy//y/z//".to_string()
+        "+^.8458&ytz//&|xy/z|&xyy/t///x|&.9|xy//y/z//".to_string()
+    );
+
+    Ok(())
+}
+
+fn get_expr_str_width(expr: String, width: u8) -> Result<String, Error> {
+    let arena = ArenaFactory::new_arena_width(&mut parse_rpn(expr).unwrap(), width)?;
+    Ok(arena.print()?)
+}
+
+#[test]
+fn test_width() -> Result<(), Error> {
+    let res = get_expr_str_width("x+255+1".to_string(), 8)?;
+    assert_eq!(res, "+0x/".to_string());
+
+    let res = get_expr_str_width("x-3".to_string(), 8)?;
+    assert_eq!(res, "+253x/".to_string());
+
+    let res = get_expr_str_width("x-3".to_string(), 16)?;
+    assert_eq!(res, "+65533x/".to_string());
+
+    let res = get_expr_str_width("x-1".to_string(), 64)?;
+    assert_eq!(res, "+18446744073709551615x/".to_string());
+
+    let res = get_expr_str_width("x.4294967295.4294967295".to_string(), 32)?;
+    assert_eq!(res, ".1x/".to_string());
+
+    let res = get_expr_str_width("x+1".to_string(), 65);
+    assert_eq!(res, Err(Error::Arena(ArenaError::WrongWidth())));
+
+    Ok(())
+}
+
+#[test]
+fn test_names() -> Result<(), Error> {
+    let res = get_expr_str("rax+rbx+rax".to_string())?;
+    assert_eq!(res, "+{rax}{rbx}{rax}/".to_string());
+
+    let res = get_expr_str("(var_1c^x)+2.arg0".to_string())?;
+    assert_eq!(res, "+^{var_1c}x/.2{arg0}//".to_string());
+
+    Ok(())
+}
+
+#[test]
+fn test_literals() -> Result<(), Error> {
+    let res = get_expr_str_width("x+0xffffffff".to_string(), 32)?;
+    assert_eq!(res, "+4294967295x/".to_string());
+
+    let res = get_expr_str_width("x^0x5bd1e995U".to_string(), 32)?;
+    assert_eq!(res, "^1540483477x/".to_string());
+
+    let res = get_expr_str_width("0b1010.x+0X1Fu".to_string(), 8)?;
+    assert_eq!(res, "+31.10x//".to_string());
+
+    let res = get_expr_str_width("x+017+12ULL+3l".to_string(), 64)?;
+    assert_eq!(res, "+30x/".to_string());
+
+    let res = get_expr_str_width("x-0x80".to_string(), 8)?;
+    assert_eq!(res, "+128x/".to_string());
+
+    let res = get_expr_str_width("x+0x100".to_string(), 8);
+    let err = ArenaError::LiteralOutOfRange {
+        value: 0x100,
+        width: 8,
+    };
+    assert_eq!(res, Err(Error::Arena(err)));
+
+    let res = get_expr_str_width("0x1ff^x".to_string(), 8);
+    let err = ArenaError::LiteralOutOfRange {
+        value: 0x1ff,
+        width: 8,
+    };
+    assert_eq!(res, Err(Error::Arena(err)));
+
+    Ok(())
+}
+
+#[test]
+fn test_tokens() -> Result<(), Error> {
+    // x - 3.y, fed without parsing
+    let mut rpn: VecDeque<Token> = VecDeque::from(vec![
+        Token::Var("x".to_string()),
+        Token::Const(3),
+        Token::Var("y".to_string()),
+        Token::BinOp('.'),
+        Token::UnaryOp('-'),
+        Token::BinOp('+'),
+    ]);
+    let arena = ArenaFactory::new_arena(&mut rpn)?;
+    assert_eq!(arena.print()?, "+x-(.3y/)/".to_string());
+
+    let mut rpn = VecDeque::from(vec![Token::Var("1x".to_string())]);
+    assert!(matches!(
+        ArenaFactory::new_arena(&mut rpn),
+        Err(Error::VarError())
+    ));
+
+    let mut rpn = VecDeque::from(vec![Token::Var("x".to_string()), Token::BinOp('+')]);
+    assert!(matches!(
+        ArenaFactory::new_arena(&mut rpn),
+        Err(Error::RPNSyntaxError())
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_hash_consing() -> Result<(), Error> {
+    let mut rpn = parse_rpn("(x&y)+3.(x&y)+(x&y)".to_string())?;
+    let arena = ArenaFactory::new_arena(&mut rpn)?;
+    assert_eq!(arena.print()?, "+&xy/.3&xy//&xy//".to_string());
+    // x, y, x&y, 3.(x&y) and the sum
+    assert_eq!(arena.num_elems(), 5);
+
+    let ch = arena.get_ch(arena.root_node)?;
+    assert_eq!(ch[0], ch[2]);
+    assert_eq!(arena.get_pars(ch[0])?.len(), 3);
+
+    // signed leaves differ
+    let mut rpn = parse_rpn("x^~x^x".to_string())?;
+    let arena = ArenaFactory::new_arena(&mut rpn)?;
+    assert_eq!(arena.num_elems(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_shared_removal() -> Result<(), Error> {
+    fn leaf(arena: &mut Arena, name: &str) -> Result<usize, ArenaError> {
+        let val = arena.intern(name);
+        arena.leaf(VarTerm {
+            val,
+            sign: String::new(),
+        })
+    }
+
+    let mut arena = Arena::new();
+    let (x, y, z) = (
+        leaf(&mut arena, "x")?,
+        leaf(&mut arena, "y")?,
+        leaf(&mut arena, "z")?,
+    );
+
+    // (x^y) + ((x^y)&z), x^y being shared
+    let xor = arena.node_with(String::new(), '^', None, &[y, x])?;
+    let and = arena.node_with(String::new(), '&', None, &[z, xor])?;
+    arena.root_node = arena.node_with(String::new(), '+', None, &[and, xor])?;
+    assert_eq!(arena.print()?, "+^xy/&^xy/z//".to_string());
+    assert_eq!(arena.node_with(String::new(), '^', None, &[y, x])?, xor);
+
+    // x^y is still used by the sum, z moved into the slot of the '&'
+    arena.replace(and, z)?;
+    assert_eq!(arena.print()?, "+^xy/z/".to_string());
+    assert_eq!(arena.get_pars(xor)?, vec![arena.root_node]);
+    assert_eq!(arena.num_elems(), 5);
+    assert_eq!(leaf(&mut arena, "z")?, and);
+
+    Ok(())
+}
+
+#[test]
+fn test_compact() -> Result<(), Error> {
+    let mut rpn = parse_rpn("z + (x|y)+(x&y)-y".to_string())?;
+    let mut arena = ArenaFactory::new_arena(&mut rpn)?;
+    let root = arena.root_node;
+    let old_elems = arena.get_preorder(root, false)?;
+    simplify(&mut arena, root)?;
+    assert_eq!(arena.print()?, "+xz/".to_string());
+    let num_elems = arena.num_elems();
+
+    // a freed slot is reused
+    let val = arena.intern("t");
+    let t_idx = arena.leaf(VarTerm {
+        val,
+        sign: String::new(),
+    })?;
+    assert!(old_elems.contains(&t_idx));
+    assert_eq!(arena.num_elems(), num_elems + 1);
+    arena.remove_subtree(t_idx)?;
+
+    let remap = arena.compact();
+    assert_eq!(arena.num_elems(), num_elems);
+    assert_eq!(remap.iter().flatten().count(), num_elems);
+    assert!((0..num_elems).all(|idx| arena.get(idx).is_ok()));
+    assert_eq!(remap[root], Some(arena.root_node));
+    assert_eq!(arena.print()?, "+xz/".to_string());
+
+    // hash-consing still finds the renumbered elements
+    let val = arena.intern("x");
+    let x_idx = arena.leaf(VarTerm {
+        val,
+        sign: String::new(),
+    })?;
+    assert_eq!(arena.num_elems(), num_elems);
+    assert!(arena.get_ch(arena.root_node)?.contains(&x_idx));
+
+    Ok(())
+}
+
+#[test]
+fn test_elem_id() -> Result<(), Error> {
+    let mut rpn = parse_rpn("(x|y)+(x&y)-y".to_string())?;
+    let mut arena = ArenaFactory::new_arena(&mut rpn)?;
+    let root = arena.elem_id(arena.root_node)?;
+    let ch = arena.get_ch(root)?;
+    let or_id = arena.elem_id(ch[ch.len() - 1])?;
+    assert_eq!(arena.get_sign(or_id)?, String::new());
+    assert!(arena.is_bitwise(or_id)?);
+
+    // the sum is simplified in place, its operands are freed
+    let root_idx = arena.root_node;
+    simplify(&mut arena, root_idx)?;
+    assert_eq!(arena.print()?, "x".to_string());
+    assert!(arena.get(root).is_ok());
+    assert_eq!(
+        arena.is_bitwise(or_id),
+        Err(ArenaError::StaleElem(or_id.idx()))
+    );
+
+    // the freed slots are reused, the handle stays stale
+    let mut new_idx = Vec::new();
+    for name in ["t", "u", "v", "w"] {
+        let val = arena.intern(name);
+        new_idx.push(arena.leaf(VarTerm {
+            val,
+            sign: String::new(),
+        })?);
+    }
+    assert!(new_idx.contains(&or_id.idx()));
+    assert!(arena.get(or_id.idx()).is_ok());
+    assert!(arena.get(or_id).is_err());
+
+    // compacting renumbers everything
+    arena.compact();
+    assert_eq!(
+        arena.get(root).err(),
+        Some(ArenaError::StaleElem(root.idx()))
     );
 
     Ok(())
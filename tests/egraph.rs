@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use dobf::egraph::{node_cost, optimize, EGraph, SaturationConfig, StopReason};
+use dobf::equiv::{check, check_arenas, EquivConfig, EquivResult};
+use dobf::error::Result;
+use dobf::expr::arena::Arena;
+use dobf::expr::factory::ArenaFactory;
+use dobf::parser::parse_rpn;
+use dobf::rewrite::mba_rules;
+
+fn new_arena(expr: &str) -> Result<Arena> {
+    ArenaFactory::new_arena_width(&mut parse_rpn(expr.to_string())?, 8)
+}
+
+// Cheapest term found, checked to be equivalent to expr
+fn optimize_expr(expr: &str, cfg: &SaturationConfig) -> Result<(String, StopReason)> {
+    let arena = new_arena(expr)?;
+    let (res, report) = optimize(&arena, arena.root_node, &mba_rules()?, cfg)?;
+    let equiv_cfg = EquivConfig {
+        max_exhaustive_bits: 16,
+        ..EquivConfig::default()
+    };
+    let equiv = check(&arena, arena.root_node, &res, res.root_node, &equiv_cfg)?;
+    assert!(!matches!(equiv, EquivResult::Counterexample(_)));
+    Ok((res.print()?, report.stop))
+}
+
+#[test]
+fn test_optimize() -> Result<()> {
+    // the first synthesis builds its tables, in debug it may take a while
+    let cfg = SaturationConfig {
+        time_limit: Duration::from_secs(60),
+        ..SaturationConfig::default()
+    };
+    assert_eq!(
+        optimize_expr("(x|y)+(x&y)-y", &cfg)?,
+        ("x".to_string(), StopReason::Saturated)
+    );
+    // layered: the inner sums are rewritten before the outer one
+    assert_eq!(
+        optimize_expr("(((x^y)+2.(x&y))|z)+(((x^y)+2.(x&y))&z)", &cfg)?.0,
+        "+zyx/"
+    );
+    assert_eq!(optimize_expr("(x|y)+(x&y)+5", &cfg)?.0, "+5yx/");
+
+    // forms of the linear MBA solver
+    assert_eq!(optimize_expr("-3.(x^y)+3.x+3.y", &cfg)?.0, ".6&yx//");
+    let cfg = SaturationConfig {
+        linear_mba: false,
+        ..cfg
+    };
+    assert_eq!(
+        optimize_expr("-3.(x^y)+3.x+3.y", &cfg)?.0,
+        "+.3y/.3x/.253^yx///"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_limits() -> Result<()> {
+    let expr = "((x^y)+2.(x&y)).3+((x|y)-(x&y))-4.(x&y)+(x|y)+(x&y)";
+    let cfg = SaturationConfig {
+        max_nodes: 20,
+        ..SaturationConfig::default()
+    };
+    assert_eq!(optimize_expr(expr, &cfg)?.1, StopReason::NodeLimit);
+
+    let cfg = SaturationConfig {
+        time_limit: Duration::from_secs(0),
+        ..SaturationConfig::default()
+    };
+    assert_eq!(optimize_expr(expr, &cfg)?.1, StopReason::TimeLimit);
+
+    let cfg = SaturationConfig {
+        max_iters: 1,
+        time_limit: Duration::from_secs(60),
+        ..SaturationConfig::default()
+    };
+    assert_eq!(optimize_expr(expr, &cfg)?.1, StopReason::IterLimit);
+
+    Ok(())
+}
+
+#[test]
+fn test_egraph() -> Result<()> {
+    let arena = new_arena("(x^y)+2.(x&y)")?;
+    let mut egraph = EGraph::new(8);
+    let root = egraph.add_arena(&arena, arena.root_node)?;
+    let num_nodes = egraph.num_nodes();
+
+    // the original term is kept
+    egraph.saturate(&mba_rules()?, &SaturationConfig::default())?;
+    assert!(egraph.num_nodes() > num_nodes);
+    assert_eq!(egraph.extract(root, node_cost)?.print()?, "+yx/");
+    let largest = egraph.extract(root, |n| 100 - node_cost(n))?;
+    assert_eq!(check_arenas(&arena, &largest)?, EquivResult::Exhaustive());
+
+    // congruence: x+y is merged with its equivalent, and so is (x+y)^z
+    let other = new_arena("(x+y)^z")?;
+    let other_id = egraph.add_arena(&other, other.root_node)?;
+    let arena = new_arena("((x^y)+2.(x&y))^z")?;
+    assert_eq!(egraph.add_arena(&arena, arena.root_node)?, other_id);
+
+    Ok(())
+}
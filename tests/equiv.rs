@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use dobf::equiv::{check, check_arenas, EquivConfig, EquivResult};
+use dobf::error::ArenaError;
+use dobf::expr::arena::Arena;
+use dobf::expr::factory::ArenaFactory;
+use dobf::parser::parse_rpn;
+use dobf::reduce::simplify;
+
+fn new_arena(expr: &str, width: u8) -> Arena {
+    ArenaFactory::new_arena_width(&mut parse_rpn(expr.to_string()).unwrap(), width)
+        .expect("rpn should be valid")
+}
+
+fn equiv(e1: &str, e2: &str, width: u8) -> Result<EquivResult, ArenaError> {
+    check_arenas(&new_arena(e1, width), &new_arena(e2, width))
+}
+
+#[test]
+fn test_equiv_exhaustive() -> Result<(), ArenaError> {
+    assert_eq!(equiv("(x^y)+2.(x&y)", "x+y", 8)?, EquivResult::Exhaustive());
+    assert_eq!(equiv("(x|y)-(x&y)", "x^y", 8)?, EquivResult::Exhaustive());
+    assert_eq!(equiv("x+~x", "y|~y", 8)?, EquivResult::Exhaustive());
+
+    let res = equiv("x|y", "x+y", 8)?;
+    let exp: BTreeMap<String, u64> = [("x", 1), ("y", 1)]
+        .iter()
+        .map(|&(v, c)| (v.to_string(), c))
+        .collect();
+    assert_eq!(res, EquivResult::Counterexample(exp));
+
+    Ok(())
+}
+
+#[test]
+fn test_equiv_sampled() -> Result<(), ArenaError> {
+    let cfg = EquivConfig {
+        num_samples: 1000,
+        ..EquivConfig::default()
+    };
+    let a1 = new_arena("(x^y)+2.(x&y)+z", 32);
+    let a2 = new_arena("x+y+z", 32);
+    let res = check(&a1, a1.root_node, &a2, a2.root_node, &cfg)?;
+    assert!(matches!(res, EquivResult::Probable(n) if n > 1000));
+    assert!(format!("{}", res).starts_with("probably equivalent"));
+
+    let res = equiv("x+y", "(x^y)+2.(x&y)", 64)?;
+    assert!(matches!(res, EquivResult::Probable(_)));
+
+    // only differ on the msb
+    let res = equiv("x.2147483648", "x.2147483648+(x&1).2147483648", 32)?;
+    assert!(matches!(res, EquivResult::Counterexample(_)));
+
+    Ok(())
+}
+
+#[test]
+fn test_equiv_width_one() -> Result<(), ArenaError> {
+    // corner values are sampled, they must fit in 1 bit
+    let cfg = EquivConfig {
+        max_exhaustive_bits: 0,
+        ..EquivConfig::default()
+    };
+    let a1 = new_arena("(x|y)+(z^t)", 1);
+    let a2 = new_arena("x+y+z+t", 1);
+    match check(&a1, a1.root_node, &a2, a2.root_node, &cfg)? {
+        EquivResult::Counterexample(vals) => assert!(vals.values().all(|&v| v <= 1)),
+        res => panic!("unexpected {}", res),
+    }
+
+    let a1 = new_arena("x^y", 1);
+    let a2 = new_arena("x+y", 1);
+    let res = check(&a1, a1.root_node, &a2, a2.root_node, &cfg)?;
+    assert!(matches!(res, EquivResult::Probable(_)));
+
+    Ok(())
+}
+
+#[test]
+fn test_equiv_simplify() -> Result<(), ArenaError> {
+    for expr in [
+        "(x|y)+(x&y)-y",
+        "x+(x&y)+(x|y)-(x^y)",
+        "(x^y)+2.(x&y)+(z^t)+2.(z&t)",
+    ] {
+        let orig = new_arena(expr, 8);
+        let mut arena = new_arena(expr, 8);
+        let root = arena.root_node;
+        simplify(&mut arena, root).unwrap();
+        assert!(!matches!(
+            check_arenas(&orig, &arena)?,
+            EquivResult::Counterexample(_)
+        ));
+    }
+
+    Ok(())
+}
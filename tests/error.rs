@@ -0,0 +1,150 @@
+use std::collections::VecDeque;
+use std::error::Error as _;
+
+use dobf::error::{ArenaError, Error, ParseError, Span};
+use dobf::expr::arena::{Arena, PnElem};
+use dobf::expr::factory::ArenaFactory;
+use dobf::expr::node::VarTerm;
+use dobf::lexer::Token;
+use dobf::parser::parse_rpn;
+use dobf::reduce::{compute_truth_table, mobius_transform, simplify, TruthTable};
+
+// Parse, build and simplify, the error of any step is returned
+fn run(line: &str) -> Result<String, Error> {
+    let mut arena = ArenaFactory::new_arena(&mut parse_rpn(line.to_string())?)?;
+    let root = arena.root_node;
+    simplify(&mut arena, root)?;
+    Ok(arena.print()?)
+}
+
+#[test]
+fn test_display() {
+    let e = Error::from(ParseError::MissOpenPar(Span::new(2, 3)));
+    assert_eq!(
+        e.to_string(),
+        "missing opening parenthesis at 2..3: this `)` has no matching `(`"
+    );
+    assert!(e.source().is_some());
+
+    let e = Error::from(ArenaError::NoElem(7));
+    assert_eq!(e.to_string(), "no element at index 7");
+    assert!(e.source().is_some());
+
+    let e = Error::TooManyVars(17);
+    assert_eq!(e.to_string(), "17 variables, at most 16 are supported");
+    assert!(e.source().is_none());
+
+    let e = Error::NoSignature(3);
+    assert_eq!(e.to_string(), "element 3 has no input or more than 16");
+    assert!(e.source().is_none());
+}
+
+#[test]
+fn test_no_panic() {
+    let lines = [
+        "",
+        " ",
+        "(",
+        ")",
+        "()",
+        "x+",
+        "+x",
+        "~",
+        "-",
+        "x y",
+        "x+(y",
+        "x+y)",
+        "3",
+        "3+4",
+        "-3",
+        "x$y",
+        "0x",
+        "1abc",
+        "x^0x1ffffffff",
+        "x..y",
+        "((((x))))",
+        "~~~~x",
+        "-(-(-x))",
+        "x+y-(x^y)",
+        "(x|y)+(x&y)-y",
+        "é+ü",
+    ];
+    for line in lines {
+        let _ = run(line);
+    }
+
+    assert!(matches!(run("3+4"), Err(Error::RPNSyntaxError())));
+    assert!(matches!(run("x+("), Err(Error::Parse(_))));
+    assert_eq!(run("x+y-(x^y)"), Ok(".2&xy//".to_string()));
+}
+
+#[test]
+fn test_arena_errors() {
+    let mut rpn = parse_rpn("x+y".to_string()).unwrap();
+    let mut arena = ArenaFactory::new_arena(&mut rpn).unwrap();
+    assert_eq!(arena.get(42).err(), Some(ArenaError::NoElem(42)));
+    assert_eq!(arena.get_sign(42), Err(ArenaError::NoElem(42)));
+    assert!(simplify(&mut arena, 42).is_err());
+    assert_eq!(
+        arena.node(String::new(), '$'),
+        Err(ArenaError::UnknownOp('$'))
+    );
+    assert_eq!(
+        arena.node("!".to_string(), '+'),
+        Err(ArenaError::UnknownSign("!".to_string()))
+    );
+    let val = VarTerm {
+        val: 9,
+        sign: String::new(),
+    };
+    assert_eq!(arena.leaf(val), Err(ArenaError::UnknownVar(9)));
+    assert_eq!(arena.var_name(9), Err(ArenaError::UnknownVar(9)));
+
+    let arena = Arena::new();
+    assert!(arena.print().is_err());
+
+    let mut rpn = VecDeque::from(vec![Token::BinOp('+')]);
+    assert_eq!(
+        ArenaFactory::new_arena(&mut rpn).err(),
+        Some(Error::RPNSyntaxError())
+    );
+}
+
+#[test]
+fn test_truth_table_errors() {
+    assert_eq!(TruthTable::new(17), Err(Error::TooManyVars(17)));
+    assert_eq!(
+        TruthTable::var(2, 2),
+        Err(Error::Arena(ArenaError::UnknownVar(2)))
+    );
+    assert_eq!(
+        TruthTable::from_rows(&[0, 1, 1]),
+        Err(Error::NotPowerOfTwo(3))
+    );
+    assert_eq!(
+        mobius_transform(&mut [0, 1, 1]),
+        Err(Error::NotPowerOfTwo(3))
+    );
+
+    assert_eq!(compute_truth_table(&[]), Err(Error::EmptyPn()));
+    let pn = vec![
+        (PnElem::Var(0), String::new()),
+        (PnElem::Var(1), String::new()),
+        (PnElem::Op('+'), String::new()),
+    ];
+    assert_eq!(
+        compute_truth_table(&pn),
+        Err(Error::Arena(ArenaError::UnknownOp('+')))
+    );
+    let pn = vec![(PnElem::Var(0), "-".to_string())];
+    assert_eq!(
+        compute_truth_table(&pn),
+        Err(Error::SignInBitwise("-".to_string()))
+    );
+    let pn = vec![
+        (PnElem::Cst(3), String::new()),
+        (PnElem::Var(0), String::new()),
+        (PnElem::Op('^'), String::new()),
+    ];
+    assert_eq!(compute_truth_table(&pn), Err(Error::CstInBitwise(3)));
+}
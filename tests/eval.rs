@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use dobf::error::ArenaError;
+use dobf::expr::arena::Arena;
+use dobf::expr::factory::ArenaFactory;
+use dobf::parser::parse_rpn;
+
+fn new_arena(expr: &str, width: u8) -> Arena {
+    ArenaFactory::new_arena_width(&mut parse_rpn(expr.to_string()).unwrap(), width)
+        .expect("rpn should be valid")
+}
+
+fn eval(expr: &str, width: u8, vals: &[(&str, u64)]) -> Result<u64, ArenaError> {
+    let arena = new_arena(expr, width);
+    let vals: HashMap<String, u64> = vals.iter().map(|&(v, c)| (v.to_string(), c)).collect();
+    arena.eval(arena.root_node, &vals)
+}
+
+#[test]
+fn test_eval_ops() -> Result<(), ArenaError> {
+    let vals = [("x", 3), ("y", 0xdeadbeef)];
+    assert_eq!(eval("x", 32, &vals)?, 3);
+    assert_eq!(eval("x+y", 32, &vals)?, 0xdeadbef2);
+    assert_eq!(eval("x.y", 32, &vals)?, 0x9c093ccd);
+    assert_eq!(eval("x^y", 32, &vals)?, 0xdeadbeec);
+    assert_eq!(eval("x&y", 32, &vals)?, 3);
+    assert_eq!(eval("x|y", 32, &vals)?, 0xdeadbeef);
+    assert_eq!(eval("x+y+5", 32, &vals)?, 0xdeadbef7);
+    assert_eq!(eval("(x^y)+2.(x&y)", 32, &vals)?, 0xdeadbef2);
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_sign() -> Result<(), ArenaError> {
+    let vals = [("x", 3), ("y", 5)];
+    assert_eq!(eval("x-y", 32, &vals)?, 0xfffffffe);
+    assert_eq!(eval("x-y", 8, &vals)?, 0xfe);
+    assert_eq!(eval("x+~y", 8, &vals)?, 0xfd);
+    assert_eq!(eval("x-~y", 8, &vals)?, 9);
+    assert_eq!(eval("~~x", 8, &vals)?, 3);
+    assert_eq!(eval("-(x^y)+x", 16, &vals)?, 0xfffd);
+    assert_eq!(eval("~(x|y)+y", 16, &vals)?, 0xfffd);
+    assert_eq!(eval("x-3", 64, &vals)?, 0);
+    assert_eq!(eval("x-4", 64, &vals)?, u64::MAX);
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_missing_var() {
+    assert_eq!(
+        eval("x+z", 32, &[("x", 1)]),
+        Err(ArenaError::MissingVar("z".to_string()))
+    );
+}
+
+#[test]
+fn test_eval_batch() -> Result<(), ArenaError> {
+    let arena = new_arena("(x|y)+(x&y)-y", 8);
+    let inputs: Vec<Vec<u64>> = (0..256)
+        .flat_map(|x| (0..256).map(move |y| vec![x, y]))
+        .collect();
+    let res = arena.eval_batch(arena.root_node, &["x", "y"], &inputs)?;
+    assert_eq!(res, inputs.iter().map(|i| i[0]).collect::<Vec<u64>>());
+
+    Ok(())
+}
+
+#[test]
+fn test_eval_names() -> Result<(), ArenaError> {
+    let vals = [("rax", 3), ("var_1c", 5), ("arg0", 0x10)];
+    assert_eq!(eval("rax+var_1c", 32, &vals)?, 8);
+    assert_eq!(eval("(rax^arg0)-2.var_1c", 8, &vals)?, 0x9);
+    assert_eq!(eval("rax.arg0+3", 8, &vals)?, 0x33);
+
+    Ok(())
+}
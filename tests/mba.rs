@@ -1,47 +1,61 @@
 use dobf::error::ArenaError;
 use dobf::expr::factory::ArenaFactory;
+use dobf::expr::node::VarTerm;
 use dobf::parser::parse_rpn;
 
 fn is_bitwise(expr: String) -> bool {
     let arena =
         ArenaFactory::new_arena(&mut parse_rpn(expr).unwrap()).expect("rpn should be valid");
-    arena.is_bitwise(arena.root_node)
+    arena.is_bitwise(arena.root_node).unwrap()
 }
 
 fn is_mba(expr: String) -> bool {
     let arena =
         ArenaFactory::new_arena(&mut parse_rpn(expr).unwrap()).expect("rpn should be valid");
-    arena.is_mba(arena.root_node)
+    arena.is_mba(arena.root_node).unwrap()
+}
+
+fn is_product(expr: String) -> bool {
+    let arena =
+        ArenaFactory::new_arena(&mut parse_rpn(expr).unwrap()).expect("rpn should be valid");
+    arena.is_product(arena.root_node).unwrap()
+}
+
+fn is_poly_mba(expr: String) -> bool {
+    let arena =
+        ArenaFactory::new_arena(&mut parse_rpn(expr).unwrap()).expect("rpn should be valid");
+    arena.is_poly_mba(arena.root_node).unwrap()
 }
 
 #[test]
 fn test_bitwise() -> Result<(), ArenaError> {
     let expr = "x+y+z+t".to_string();
-    assert_eq!(is_bitwise(expr), false);
+    assert!(!is_bitwise(expr));
 
+    // a cst is one more bitwise operand
     let expr = "32^a".to_string();
-    assert_eq!(is_bitwise(expr), false);
+    assert!(is_bitwise(expr));
 
     let expr = "x+y^a".to_string();
-    assert_eq!(is_bitwise(expr), false);
+    assert!(!is_bitwise(expr));
 
     let expr = "x^y^(t.y)".to_string();
-    assert_eq!(is_bitwise(expr), false);
+    assert!(!is_bitwise(expr));
 
     let expr = "x^y^(t|y^(t|a))".to_string();
-    assert_eq!(is_bitwise(expr), true);
+    assert!(is_bitwise(expr));
 
     let expr = "x".to_string();
-    assert_eq!(is_bitwise(expr), true);
+    assert!(is_bitwise(expr));
 
     let expr = "x^y^(t|y)".to_string();
-    assert_eq!(is_bitwise(expr), true);
+    assert!(is_bitwise(expr));
 
     let expr = "x^y^(t|y)".to_string();
-    assert_eq!(is_bitwise(expr), true);
+    assert!(is_bitwise(expr));
 
     let expr = "x^y^(t|y^(t+a))".to_string();
-    assert_eq!(is_bitwise(expr), false);
+    assert!(!is_bitwise(expr));
 
     Ok(())
 }
@@ -49,61 +63,115 @@ fn test_bitwise() -> Result<(), ArenaError> {
 #[test]
 fn test_mba() -> Result<(), ArenaError> {
     let expr = "x+y.z".to_string();
-    assert_eq!(is_mba(expr), false);
+    assert!(!is_mba(expr));
 
     let expr = "x+(z^(a+b))".to_string();
-    assert_eq!(is_mba(expr), false);
+    assert!(!is_mba(expr));
 
     let expr = "x+(z^4)".to_string();
-    assert_eq!(is_mba(expr), false);
+    assert!(is_mba(expr));
 
     let expr = "x^y".to_string();
-    assert_eq!(is_mba(expr), false);
+    assert!(!is_mba(expr));
 
     let expr = "3.(x^y)".to_string();
-    assert_eq!(is_mba(expr), false);
+    assert!(!is_mba(expr));
 
     let expr = "x+4.(z^3.x)".to_string();
-    assert_eq!(is_mba(expr), false);
+    assert!(!is_mba(expr));
 
     let expr = "x+y+4&z".to_string();
-    assert_eq!(is_mba(expr), false);
+    assert!(is_mba(expr));
 
     let expr = "x+y+4.z.a".to_string();
-    assert_eq!(is_mba(expr), false);
+    assert!(!is_mba(expr));
 
     let expr = "x+y+4.(z^x).(a^b)".to_string();
-    assert_eq!(is_mba(expr), false);
+    assert!(!is_mba(expr));
 
     let expr = "x".to_string();
-    assert_eq!(is_mba(expr), false);
+    assert!(!is_mba(expr));
 
     let expr = "x+y".to_string();
-    assert_eq!(is_mba(expr), true);
+    assert!(is_mba(expr));
 
     let expr = "x+x^y".to_string();
-    assert_eq!(is_mba(expr), true);
+    assert!(is_mba(expr));
 
     let expr = "a+x^(y|t)".to_string();
-    assert_eq!(is_mba(expr), true);
+    assert!(is_mba(expr));
 
     let expr = "x+13".to_string();
-    assert_eq!(is_mba(expr), true);
+    assert!(is_mba(expr));
 
     let expr = "x+y+z".to_string();
-    assert_eq!(is_mba(expr), true);
+    assert!(is_mba(expr));
 
     let expr = "x+y^z".to_string();
-    assert_eq!(is_mba(expr), true);
+    assert!(is_mba(expr));
 
     let expr = "x+y+4.z".to_string();
-    assert_eq!(is_mba(expr), true);
+    assert!(is_mba(expr));
 
     let expr = "x+y+4.(z^x)".to_string();
-    assert_eq!(is_mba(expr), true);
+    assert!(is_mba(expr));
 
     let expr = "x+y+4.(z^(x|t))".to_string();
-    assert_eq!(is_mba(expr), true);
+    assert!(is_mba(expr));
+
+    Ok(())
+}
+
+#[test]
+fn test_poly_mba() -> Result<(), ArenaError> {
+    let expr = "(x&y).(x|y)".to_string();
+    assert!(is_product(expr));
+
+    let expr = "3.x.(y^~z)".to_string();
+    assert!(is_product(expr));
+
+    let expr = "3.(x^y)".to_string();
+    assert!(!is_product(expr));
+
+    let expr = "x.(y+z)".to_string();
+    assert!(!is_product(expr));
+
+    let expr = "x.y+(x&~y).(~x&y)".to_string();
+    assert!(is_poly_mba(expr));
+
+    let expr = "x+y+4.(z^x).(a^b)".to_string();
+    assert!(is_poly_mba(expr));
+
+    let expr = "x+y+4.(z^x)".to_string();
+    assert!(!is_poly_mba(expr));
+
+    let expr = "x.y+(z.(x+y))".to_string();
+    assert!(!is_poly_mba(expr));
+
+    Ok(())
+}
+
+#[test]
+fn test_reclassify() -> Result<(), ArenaError> {
+    let mut arena =
+        ArenaFactory::new_arena(&mut parse_rpn("((x|y)-(x&y))^z".to_string()).unwrap()).unwrap();
+    let root = arena.root_node;
+    assert!(!arena.is_bitwise(root)?);
+
+    // the sum rewritten as a bitwise node, its parent is stale until
+    // reclassified
+    let sum_idx = arena.get_ch(root)?[1];
+    assert!(arena.is_mba(sum_idx)?);
+    let x = arena.intern("x");
+    let leaf_idx = arena.leaf(VarTerm {
+        val: x,
+        sign: String::new(),
+    })?;
+    arena.replace(sum_idx, leaf_idx)?;
+    assert!(!arena.is_bitwise(root)?);
+    assert!(arena.reclassify(root)?);
+    assert!(arena.is_bitwise(root)?);
+    assert!(!arena.reclassify(root)?);
 
     Ok(())
 }
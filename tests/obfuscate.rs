@@ -0,0 +1,107 @@
+use dobf::equiv::{check_arenas, EquivResult};
+use dobf::expr::arena::Arena;
+use dobf::expr::factory::ArenaFactory;
+use dobf::obfuscate::{obfuscate, ObfConfig};
+use dobf::parser::parse_rpn;
+use dobf::reduce::{simplify, simplify_fixpoint, MAX_PASSES};
+
+fn new_arena(expr: &str, width: u8) -> Arena {
+    ArenaFactory::new_arena_width(&mut parse_rpn(expr.to_string()).unwrap(), width)
+        .expect("valid rpn")
+}
+
+// The obfuscated expr is parsed back and checked on all inputs, the width
+// being small enough for exhaustive checks
+fn test_obfuscate_expr(expr: &str, width: u8, cfg: &ObfConfig) -> String {
+    let orig = new_arena(expr, width);
+    let res = obfuscate(&orig, orig.root_node, cfg).unwrap();
+    let arena = new_arena(&res, width);
+    assert_eq!(
+        check_arenas(&arena, &orig).unwrap(),
+        EquivResult::Exhaustive()
+    );
+    res
+}
+
+#[test]
+fn test_obfuscate() {
+    let cfg = ObfConfig::default();
+    for expr in ["x+y", "x^y", "x", "~x", "-(x+y)", "~(x+y+3)", "(x&92)+y"] {
+        test_obfuscate_expr(expr, 8, &cfg);
+    }
+    test_obfuscate_expr("x|y|z", 4, &cfg);
+
+    let cfg = ObfConfig {
+        num_terms: 6,
+        num_layers: 2,
+        ..ObfConfig::default()
+    };
+    test_obfuscate_expr("x+y", 8, &cfg);
+    test_obfuscate_expr("(x^y)-2.(~x&z)", 4, &cfg);
+
+    let cfg = ObfConfig {
+        polynomial: true,
+        ..ObfConfig::default()
+    };
+    let res = test_obfuscate_expr("x+y", 8, &cfg);
+    assert!(res.contains(")."));
+    test_obfuscate_expr("x-(y&z)+7", 4, &cfg);
+}
+
+#[test]
+fn test_obfuscate_seed() {
+    let cfg = |seed| ObfConfig {
+        seed,
+        ..ObfConfig::default()
+    };
+    let a = test_obfuscate_expr("x+y", 8, &cfg(1));
+    assert_eq!(a, test_obfuscate_expr("x+y", 8, &cfg(1)));
+    assert_ne!(a, test_obfuscate_expr("x+y", 8, &cfg(2)));
+}
+
+#[test]
+fn test_obfuscate_simplify() {
+    let orig = new_arena("x+y", 8);
+    let res = obfuscate(&orig, orig.root_node, &ObfConfig::default()).unwrap();
+
+    let mut arena = new_arena(&res, 8);
+    let root = arena.root_node;
+    simplify(&mut arena, root).unwrap();
+    assert_eq!(arena.print().unwrap(), "+xy/");
+
+    let cfg = ObfConfig {
+        polynomial: true,
+        ..ObfConfig::default()
+    };
+    let res = obfuscate(&orig, orig.root_node, &cfg).unwrap();
+    let mut arena = new_arena(&res, 8);
+    let root = arena.root_node;
+    simplify_fixpoint(&mut arena, root, MAX_PASSES).unwrap();
+    assert_eq!(arena.print().unwrap(), "+xy/");
+}
+
+#[test]
+fn test_obfuscate_opaque() {
+    // non-linear subterms are inputs, printed back as they are
+    let cfg = ObfConfig::default();
+    let res = test_obfuscate_expr("x.(y+z)", 4, &cfg);
+    assert!(res.contains("((z+y).x)"));
+    test_obfuscate_expr("(x+y)^z", 4, &cfg);
+    test_obfuscate_expr("x.y+x+3", 4, &cfg);
+    test_obfuscate_expr("-~(x.y)+~-(x^y)", 4, &cfg);
+
+    // the sign of the root is applied to the signature, nested signs of the
+    // operands of a sum keep it linear
+    for expr in ["~-x", "~-(x^y)", "~-(x.y)", "-~-x+y", "~-(~-x+-~y)"] {
+        test_obfuscate_expr(expr, 4, &cfg);
+    }
+    let res = test_obfuscate_expr("-~-x+y", 4, &cfg);
+    assert!(!res.contains("-~-x"));
+
+    let cfg = ObfConfig {
+        num_layers: 2,
+        polynomial: true,
+        ..ObfConfig::default()
+    };
+    test_obfuscate_expr("((a|b)-(a&b))^c", 4, &cfg);
+}
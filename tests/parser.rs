@@ -1,36 +1,48 @@
-use dobf::error::ParseError;
+use dobf::error::{ParseError, Span};
+use dobf::lexer::Token;
 use dobf::parser::parse_rpn;
 
+fn seq_error(hint: &str, start: usize, end: usize) -> ParseError {
+    ParseError::WrongSeqChar(hint.to_string(), Span::new(start, end))
+}
+
+fn rpn_str(line: &str) -> Result<Vec<String>, ParseError> {
+    Ok(parse_rpn(line.to_string())?
+        .iter()
+        .map(|t| t.to_string())
+        .collect())
+}
+
 #[test]
 fn test_missing_par_simple() -> Result<(), ParseError> {
     let miss_cl = "(".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::MissClosePar("Missing )".to_string());
+    let expected = ParseError::MissClosePar(Span::new(0, 1));
     assert_eq!(res, expected);
 
     let miss_cl = ")".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::MissOpenPar("Missing (".to_string());
+    let expected = ParseError::MissOpenPar(Span::new(0, 1));
     assert_eq!(res, expected);
 
     let miss_cl = "())".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::MissOpenPar("Missing (".to_string());
+    let expected = ParseError::MissOpenPar(Span::new(2, 3));
     assert_eq!(res, expected);
 
     let miss_cl = "(()".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::MissClosePar("Missing )".to_string());
+    let expected = ParseError::MissClosePar(Span::new(0, 1));
     assert_eq!(res, expected);
 
     let miss_cl = "((x+y)".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::MissClosePar("Missing )".to_string());
+    let expected = ParseError::MissClosePar(Span::new(0, 1));
     assert_eq!(res, expected);
 
     let miss_cl = "((x+(y^z)) + t".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::MissClosePar("Missing )".to_string());
+    let expected = ParseError::MissClosePar(Span::new(0, 1));
     assert_eq!(res, expected);
 
     Ok(())
@@ -40,7 +52,7 @@ fn test_missing_par_simple() -> Result<(), ParseError> {
 fn test_missing_op() -> Result<(), ParseError> {
     let miss_cl = "x^y(t.y)".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::WrongSeqChar("wrong seq of char: y/(".to_string());
+    let expected = seq_error("`(` follows variable `y`", 3, 4);
     assert_eq!(res, expected);
 
     Ok(())
@@ -50,12 +62,12 @@ fn test_missing_op() -> Result<(), ParseError> {
 fn test_succ_op() -> Result<(), ParseError> {
     let miss_cl = "+ ^".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::WrongSeqChar("wrong seq of char: +/^".to_string());
+    let expected = seq_error("binary operator `+` has no left operand", 0, 1);
     assert_eq!(res, expected);
 
     let miss_cl = "(x+y) ^ z +((x - h)^z) -+ a".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::WrongSeqChar("wrong seq of char: -/+".to_string());
+    let expected = seq_error("binary operator `+` follows binary operator `-`", 24, 25);
     assert_eq!(res, expected);
 
     Ok(())
@@ -65,12 +77,12 @@ fn test_succ_op() -> Result<(), ParseError> {
 fn test_neg_sign_before_op() -> Result<(), ParseError> {
     let miss_cl = "~+".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::WrongSeqChar("wrong seq of char: ~/+".to_string());
+    let expected = seq_error("binary operator `+` follows sign `~`", 1, 2);
     assert_eq!(res, expected);
 
     let miss_cl = "x + y - ~~|y".to_string();
     let res = parse_rpn(miss_cl).unwrap_err();
-    let expected = ParseError::WrongSeqChar("wrong seq of char: ~/|".to_string());
+    let expected = seq_error("binary operator `|` follows sign `~`", 10, 11);
     assert_eq!(res, expected);
     Ok(())
 }
@@ -79,12 +91,12 @@ fn test_neg_sign_before_op() -> Result<(), ParseError> {
 fn test_ok_neg() -> Result<(), ParseError> {
     //need regex
     /*let miss_cl = "~~~y".to_string();
-    let res = parse_rpn(miss_cl).unwrap();
+    let res = rpn_str(&miss_cl).unwrap();
     let expected = vec!["y", "~"];
     assert_eq!(res, expected);*/
 
     /*let miss_cl = "x + y - ~~~(x^y)&((~x -y)^y)".to_string();
-    let res = parse_rpn(miss_cl).unwrap();
+    let res = rpn_str(&miss_cl).unwrap();
     let expected = vec![
         "x", "y", "+", "x", "y", "^", "~", "x", "~", "y", "-", "y", "^", "&", "-",
     ];
@@ -95,29 +107,29 @@ fn test_ok_neg() -> Result<(), ParseError> {
 #[test]
 fn test_ok() -> Result<(), ParseError> {
     let miss_cl = "y+((((x+y)^z)))".to_string();
-    let res = parse_rpn(miss_cl).unwrap();
+    let res = rpn_str(&miss_cl).unwrap();
     let expected = vec!["y", "x", "y", "+", "z", "^", "+"];
     assert_eq!(res, expected);
 
     let miss_cl = "x+y-(x^y)".to_string();
-    let res = parse_rpn(miss_cl).unwrap();
-    let expected = vec!["x", "y", "x", "y", "^", "-", "+", "+"];
+    let res = rpn_str(&miss_cl).unwrap();
+    let expected = vec!["x", "y", "+", "x", "y", "^", "-", "+"];
     assert_eq!(res, expected);
 
     let miss_cl = "x + y -(x^y)&((x -y)^y)".to_string();
-    let res = parse_rpn(miss_cl).unwrap();
+    let res = rpn_str(&miss_cl).unwrap();
     let expected = vec![
-        "x", "y", "x", "y", "^", "x", "y", "-", "+", "y", "^", "&", "-", "+", "+",
+        "x", "y", "+", "x", "y", "^", "x", "y", "-", "+", "y", "^", "&", "-", "+",
     ];
     assert_eq!(res, expected);
 
     let miss_cl = "x + (y) -(x^y+(~y))".to_string();
-    let res = parse_rpn(miss_cl).unwrap();
-    let expected = vec!["x", "y", "x", "y", "^", "y", "~", "+", "-", "+", "+"];
+    let res = rpn_str(&miss_cl).unwrap();
+    let expected = vec!["x", "y", "+", "x", "y", "^", "y", "~", "+", "-", "+"];
     assert_eq!(res, expected);
 
     let miss_cl = "8458.(y&t&z) ^( x|y&z)&((x&y )& y|t) + x+ 9.(x|y)&y|z".to_string();
-    let res = parse_rpn(miss_cl).unwrap();
+    let res = rpn_str(&miss_cl).unwrap();
     let expected = vec![
         "8458", "y", "t", "&", "z", "&", ".", "x", "y", "|", "z", "&", "x", "y", "&", "y", "&",
         "t", "|", "&", "^", "x", "+", "9", "x", "y", "|", ".", "y", "&", "z", "|", "+",
@@ -126,3 +138,115 @@ fn test_ok() -> Result<(), ParseError> {
 
     Ok(())
 }
+
+#[test]
+fn test_names() -> Result<(), ParseError> {
+    let res = rpn_str("rax + var_1c^arg0")?;
+    let expected = vec!["rax", "var_1c", "arg0", "^", "+"];
+    assert_eq!(res, expected);
+
+    let res = rpn_str("_t1.3 + ~x2")?;
+    let expected = vec!["_t1", "3", ".", "x2", "~", "+"];
+    assert_eq!(res, expected);
+
+    let res = parse_rpn("rax^rbx(t.y)".to_string()).unwrap_err();
+    let expected = seq_error("`(` follows variable `rbx`", 7, 8);
+    assert_eq!(res, expected);
+
+    // C identifiers only, spans are in bytes
+    let res = parse_rpn("x+é".to_string()).unwrap_err();
+    assert_eq!(res, ParseError::WrongChar('é', Span::new(2, 4)));
+
+    let res = parse_rpn("a١+b".to_string()).unwrap_err();
+    assert_eq!(res, ParseError::WrongChar('١', Span::new(1, 3)));
+
+    Ok(())
+}
+
+#[test]
+fn test_literals() -> Result<(), ParseError> {
+    let res = rpn_str("x^0x5bd1e995U + 0b1010.y + 12ULL")?;
+    let expected = vec!["x", "1540483477", "^", "10", "y", ".", "+", "12", "+"];
+    assert_eq!(res, expected);
+
+    let res = rpn_str("017 + 0X1Fu + 3l + 0")?;
+    let expected = vec!["15", "31", "+", "3", "+", "0", "+"];
+    assert_eq!(res, expected);
+
+    for lit in ["0b102", "09", "12UUL", "0x", "1abc"] {
+        let res = parse_rpn(format!("x+{}", lit)).unwrap_err();
+        let span = Span::new(2, 2 + lit.len());
+        assert_eq!(res, ParseError::InvalidLiteral(lit.to_string(), span));
+    }
+
+    let res = parse_rpn("x+0x10000000000000000".to_string()).unwrap_err();
+    let expected =
+        ParseError::LiteralOutOfRange("0x10000000000000000".to_string(), Span::new(2, 21));
+    assert_eq!(res, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_tokens() -> Result<(), ParseError> {
+    let res = parse_rpn("-~-x - ~3".to_string())?;
+    let expected = vec![
+        Token::Var("x".to_string()),
+        Token::UnaryOp('-'),
+        Token::UnaryOp('~'),
+        Token::UnaryOp('-'),
+        Token::Const(3),
+        Token::UnaryOp('~'),
+        Token::UnaryOp('-'),
+        Token::BinOp('+'),
+    ];
+    assert_eq!(res, expected);
+
+    // signs bind tighter than binary operators
+    let res = rpn_str("~x&y")?;
+    assert_eq!(res, vec!["x", "~", "y", "&"]);
+
+    // binary '-' is left associative
+    let res = rpn_str("a-b-c")?;
+    assert_eq!(res, vec!["a", "b", "-", "+", "c", "-", "+"]);
+
+    let res = parse_rpn("rax rbx".to_string()).unwrap_err();
+    let expected = seq_error("variable `rbx` follows variable `rax`", 4, 7);
+    assert_eq!(res, expected);
+
+    let res = parse_rpn("x+~".to_string()).unwrap_err();
+    assert_eq!(res, ParseError::DanglingNegSign(Span::new(2, 3)));
+
+    Ok(())
+}
+
+#[test]
+fn test_render() {
+    let line = "x+(^y)";
+    let res = parse_rpn(line.to_string()).unwrap_err();
+    let expected = "\
+error: unexpected token
+ --> 1:4
+  |
+1 | x+(^y)
+  |    ^ binary operator `^` follows `(`
+";
+    assert_eq!(res.render(line), expected);
+
+    let line = "(rax + 0x1g) ^ y";
+    let res = parse_rpn(line.to_string()).unwrap_err();
+    let expected = "\
+error: invalid literal
+ --> 1:8
+  |
+1 | (rax + 0x1g) ^ y
+  |        ^^^^ `0x1g` is not an integer
+";
+    assert_eq!(res.render(line), expected);
+
+    let line = "((x+y)";
+    let res = parse_rpn(line.to_string()).unwrap_err();
+    assert!(res
+        .render(line)
+        .ends_with("\n  | ^ this `(` is never closed\n"));
+}
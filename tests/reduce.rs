@@ -1,23 +1,38 @@
-use dobf::reduce::{bit_pop, comb_bitmask, compute_truth_table, solve_sierpinski};
-use std::collections::BTreeSet;
+use dobf::equiv::{check_arenas, EquivResult};
+use dobf::expr::arena::PnElem;
+use dobf::expr::factory::ArenaFactory;
+use dobf::parser::parse_rpn;
+use dobf::reduce::{
+    compute_truth_table, compute_truth_table_vars, inverse_mobius_transform, mobius_transform,
+    simplify, simplify_abstract, simplify_fixpoint, FixpointReport, TruthTable,
+};
 
-fn test_no_sign(v: Vec<char>, e: Vec<i8>) {
+// ops and '!' (close) as chars, a var id being the code of its char
+fn pn_elem(c: char) -> PnElem {
+    match c {
+        '^' | '|' | '&' => PnElem::Op(c),
+        '!' => PnElem::Close,
+        v => PnElem::Var(v as usize),
+    }
+}
+
+fn test_no_sign(v: Vec<char>, e: Vec<u8>) {
     let aux_s = String::new();
-    let mut pn: Vec<(char, String)> = v.iter().map(|x| (*x, aux_s.clone())).collect();
+    let mut pn: Vec<(PnElem, String)> = v.iter().map(|x| (pn_elem(*x), aux_s.clone())).collect();
     pn.reverse();
-    let (res, _) = compute_truth_table(&pn);
-    assert_eq!(res, e);
+    let (res, _) = compute_truth_table(&pn).unwrap();
+    assert_eq!(res.to_rows(), e);
 }
 
-fn test_sign(v: Vec<char>, s: Vec<&str>, e: Vec<i8>) {
-    let mut pn: Vec<(char, String)> = v
+fn test_sign(v: Vec<char>, s: Vec<&str>, e: Vec<u8>) {
+    let mut pn: Vec<(PnElem, String)> = v
         .iter()
-        .map(|c| c.clone())
+        .map(|x| pn_elem(*x))
         .zip(s.iter().map(|&aux_s| String::from(aux_s)))
         .collect();
     pn.reverse();
-    let (res, _) = compute_truth_table(&pn);
-    assert_eq!(res, e);
+    let (res, _) = compute_truth_table(&pn).unwrap();
+    assert_eq!(res.to_rows(), e);
 }
 
 #[test]
@@ -62,54 +77,230 @@ fn test_truth_table_sign() {
     test_sign(expr_v, expr_s, expected);
 }
 
-fn test_comb_b(n_base: u8, bitmask: u64, exp_vec: Vec<u64>) {
-    let expected = exp_vec.into_iter().collect();
-    let set_comb: BTreeSet<u64> = comb_bitmask(n_base, bitmask).into_iter().collect();
-    assert_eq!(set_comb, expected);
+#[test]
+fn test_truth_table_many_vars() {
+    let vars: Vec<usize> = (0..16).collect();
+
+    // a ^ p
+    let pn = vec![
+        (PnElem::Var(15), String::new()),
+        (PnElem::Var(0), String::new()),
+        (PnElem::Op('^'), String::new()),
+    ];
+    let tt = compute_truth_table_vars(&pn, &vars).unwrap();
+    assert_eq!(tt.num_rows(), 1 << 16);
+    for i in [0, 1, 1 << 15, (1 << 15) + 1, 0x1234, 0xffff] {
+        assert_eq!(tt.get(i), (i & 1 != 0) ^ (i >> 15 != 0));
+    }
+
+    // ~(a & b & c & d & e & f & g)
+    let mut pn: Vec<(PnElem, String)> = vars[..7]
+        .iter()
+        .map(|v| (PnElem::Var(*v), String::new()))
+        .collect();
+    pn.push((PnElem::Op('&'), "~".to_string()));
+    let tt = compute_truth_table_vars(&pn, &vars[..7]).unwrap();
+    assert_eq!(tt.to_rows().iter().filter(|&&r| r == 0).count(), 1);
+    assert!(!tt.get(127));
+}
+
+fn test_mobius_tt(rows: Vec<u8>, exp: Vec<i64>) {
+    let tt = TruthTable::from_rows(&rows).unwrap();
+    let mut v: Vec<i64> = (0..tt.num_rows()).map(|i| tt.get(i) as i64).collect();
+    mobius_transform(&mut v).unwrap();
+    assert_eq!(v, exp);
 }
 
 #[test]
-fn test_comb_bitmask() {
-    test_comb_b(3, 3, vec![0, 1, 2, 3]);
-    test_comb_b(5, 20, vec![0, 4, 16, 20]);
-    test_comb_b(5, 21, vec![0, 1, 4, 16, 5, 17, 20, 21]);
-    test_comb_b(
-        8,
-        43,
-        vec![0, 1, 2, 3, 8, 9, 32, 33, 10, 11, 35, 40, 34, 41, 42, 43],
+fn test_mobius_transform() {
+    // x | y = x + y - x&y
+    let mut v = vec![0, 1, 1, 1];
+    mobius_transform(&mut v).unwrap();
+    assert_eq!(v, vec![0, 1, 1, -1]);
+    inverse_mobius_transform(&mut v).unwrap();
+    assert_eq!(v, vec![0, 1, 1, 1]);
+
+    // coefficients over the basis of conjunctions, from the truth table
+    test_mobius_tt(vec![0, 1, 0, 1], vec![0, 1, 0, 0]);
+    test_mobius_tt(vec![1, 0, 1, 1], vec![1, -1, 0, 1]);
+    test_mobius_tt(vec![1, 1, 1, 0, 1, 1, 1, 0], vec![1, 0, 0, -1, 0, 0, 0, 0]);
+    test_mobius_tt(
+        vec![1, 1, 0, 0, 0, 0, 1, 0],
+        vec![1, 0, -1, 0, -1, 0, 2, -1],
     );
+
+    // wrapping coefficients
+    let mut v = vec![0, i64::MIN, 0, i64::MAX];
+    mobius_transform(&mut v).unwrap();
+    assert_eq!(v, vec![0, i64::MIN, 0, -1]);
+    inverse_mobius_transform(&mut v).unwrap();
+    assert_eq!(v, vec![0, i64::MIN, 0, i64::MAX]);
+
+    let orig: Vec<i64> = (0..(1 << 16))
+        .map(|i: i64| i.wrapping_mul(0x9e37_79b9))
+        .collect();
+    let mut v = orig.clone();
+    mobius_transform(&mut v).unwrap();
+    inverse_mobius_transform(&mut v).unwrap();
+    assert_eq!(v, orig);
+}
+
+fn test_simplify_expr(expr: &str, exp: &str) {
+    test_simplify_expr_width(expr, 32, exp)
+}
+
+fn test_simplify_expr_width(expr: &str, width: u8, exp: &str) {
+    let mut arena = ArenaFactory::new_arena_width(&mut parse_rpn(expr.to_string()).unwrap(), width)
+        .expect("valid rpn");
+    let root = arena.root_node;
+    simplify(&mut arena, root).unwrap();
+    assert_eq!(arena.print().unwrap(), exp);
 }
 
 #[test]
-fn test_bit_pop() {
-    assert_eq!(bit_pop(3), 2);
-    assert_eq!(bit_pop(21), 3);
-    assert_eq!(bit_pop(43), 4);
-    assert_eq!(bit_pop(63), 6);
+fn test_simplify() {
+    test_simplify_expr("(x^y)+2.(x&y)", "+xy/");
+    test_simplify_expr("(x|y)+(x&y)-y", "x");
+    test_simplify_expr("x+y-(x^y)", ".2&xy//");
+    test_simplify_expr("-3.(x^y)+3.x+3.y", ".6&xy//");
+    test_simplify_expr("x+(x&y)+(x|y)-(x^y)", "+x.2&xy///");
+    test_simplify_expr("(x^y)+2.(x&y)+(z^t)+2.(z&t)", "+txyz/");
+    test_simplify_expr("(rbx^rax)+2.(rbx&rax)", "+{rax}{rbx}/");
+    test_simplify_expr("(arg0|var_1c)-(arg0&var_1c)", "^{arg0}{var_1c}/");
+    test_simplify_expr("(a|b|c|d|e)-(a|b|c|d|e)+a", "a");
+
+    // signature is a single boolean function
+    test_simplify_expr("(x|y)-(x&y)", "^xy/");
+    test_simplify_expr("x+y-2.(x&y)", "^xy/");
+    test_simplify_expr("x+y-(x&y)", "|xy/");
+
+    // coefficients modulo 2^width
+    test_simplify_expr_width("x+y+128.(x&y)+128.(x&y)", 8, "+xy/");
+    test_simplify_expr_width("x+(x&y).255+(x&y)", 8, "x");
+    test_simplify_expr_width("x+(x&y).255+(x&y)", 16, "+x.256&xy///");
+
+    // not simpler than the original expression
+    test_simplify_expr("x+y", "+xy/");
+
+    // csts of bitwise operands, as pseudo-variables
+    test_simplify_expr("(x^92)+2.(x&92)", "+92x/");
+    test_simplify_expr("(x^0x5c)+2.(0x5c&x)", "+92x/");
+    test_simplify_expr("(x|5)-(x&5)", "^5x/");
+    test_simplify_expr("(x|0x5c)+(y&3)-(y&3)", "|92x/");
+    test_simplify_expr("(x^y^7)+2.((x^y)&7)", "+7xy-(.2&xy/)//");
+
+    // cst term, as the all-ones function of the basis
+    test_simplify_expr("3+(x^y)-2.(x&y)", "+3xy-(.4&xy/)//");
+    test_simplify_expr("(x|y)+(x&y)-y+5", "+5x/");
+    test_simplify_expr("-x-1", "~x");
+    test_simplify_expr("x+y-(x&y)-1", "+4294967295|xy//");
+    test_simplify_expr("x+~x", "+4294967295/");
+    test_simplify_expr("x+~x+1", "+0/");
+    test_simplify_expr("2+(x|y)-(x&y)-(x^y)", "+2/");
+    test_simplify_expr_width("~x+x.y+1-x.y", 8, "-x");
+
+    // products of bitwise terms, expanded over the conjunction basis
+    test_simplify_expr("(x&y).(x|y)+(x&~y).(~x&y)", ".xy/");
+    test_simplify_expr("x.y+(x&y).(x|y)+(x&~y).(~x&y)", ".2xy/");
+    test_simplify_expr("(x&y).(x|y)+(x&~y).(~x&y)+5", "+5.xy//");
+    test_simplify_expr("(x&y).(x|y)+(x&~y).(~x&y)-x.y+(x^y)+2.(x&y)", "+xy/");
+    test_simplify_expr("((x&y).(x|y)+(x&~y).(~x&y))^z", "^.xy/z/");
+    test_simplify_expr("(x&y).(x|y)", ".&xy/|xy//");
+    test_simplify_expr_width("x.y.128+x.y.128", 8, "+0/");
+
+    // shared mba simplified once, for all its uses
+    test_simplify_expr("((x^y)+2.(x&y)) & ((x^y)+2.(x&y)).3", "&+xy/.3+xy///");
+}
+
+// Simplified expression, equivalent to the original one on all inputs
+fn test_simplify_equiv(expr: &str, width: u8, exp: &str) {
+    let new_arena = || {
+        ArenaFactory::new_arena_width(&mut parse_rpn(expr.to_string()).unwrap(), width)
+            .expect("valid rpn")
+    };
+    let (mut arena, orig) = (new_arena(), new_arena());
+    let root = arena.root_node;
+    simplify(&mut arena, root).unwrap();
+    assert_eq!(arena.print().unwrap(), exp);
+    assert_eq!(
+        check_arenas(&arena, &orig).unwrap(),
+        EquivResult::Exhaustive()
+    );
+}
+
+#[test]
+fn test_simplify_semi_linear() {
+    // bit positions grouped by the bits of the csts
+    test_simplify_expr("(x&0xf0)+(x&0x0f)", "&255x/");
+    test_simplify_expr("(x&0xf0)+(x&0x0f)+3", "+3&255x//");
+    test_simplify_expr("(x^0xff00)+2.(x&0xff00)", "+65280x/");
+    test_simplify_expr(
+        "(x&0xf0f0)+3.(y|0x0f0f)+(x&0x0f0f)-3.(y&0xf0f0)",
+        "+11565&65535x/.3&4294901760y///",
+    );
+
+    // already compact
+    test_simplify_expr("(x&0xf0f0)+3.(y|0x0f0f)", "+&61680x/.3|3855y///");
+
+    test_simplify_equiv("(x&0xf0)+(x&0x0f)", 8, "x");
+    test_simplify_equiv("(x&0x0f)+(y|0xf0)+(x&0xf0)-(~y&0xf0)", 8, "+xy/");
 }
 
-fn test_solve_s(num_vars: u8, tt: Vec<i8>, exp: Vec<i8>) {
-    let s = solve_sierpinski(tt, num_vars);
-    assert_eq!(s, exp);
+fn test_abstract_expr(expr: &str, exp: &str) {
+    let mut arena =
+        ArenaFactory::new_arena(&mut parse_rpn(expr.to_string()).unwrap()).expect("valid rpn");
+    let root = arena.root_node;
+    simplify_abstract(&mut arena, root).unwrap();
+    assert_eq!(arena.print().unwrap(), exp);
 }
 
 #[test]
-fn test_solve_sierpinski() {
-    let num_vars = 2;
-    let tt = [0, 1, 0, 1];
-    let expected = [0, 0, -1, 0, 1];
-    test_solve_s(num_vars, tt.to_vec(), expected.to_vec());
-
-    let tt = [1, 0, 1, 1];
-    let expected = [-1, 0, 1, -1, 1];
-    test_solve_s(num_vars, tt.to_vec(), expected.to_vec());
-
-    let num_vars = 3;
-    let tt = [1, 1, 1, 0, 1, 1, 1, 0];
-    let expected = [0, 0, 0, 0, 1, 0, 0, -1, 1];
-    test_solve_s(num_vars, tt.to_vec(), expected.to_vec());
-
-    let tt = [1, 1, 0, 0, 0, 0, 1, 0];
-    let expected = [1, -2, 0, 1, 0, 1, 0, -1, 1];
-    test_solve_s(num_vars, tt.to_vec(), expected.to_vec());
+fn test_simplify_abstract() {
+    // non-bitwise subterms as fresh variables
+    test_abstract_expr("((a+b)^c)+2.((a+b)&c)", "+abc/");
+    test_abstract_expr("((a+b)|c)+((a+b)&c)-c", "+ab/");
+    test_abstract_expr("x.(a+b)+y-x.(a+b)", "y");
+    test_abstract_expr("(x+y).(x+y)+(x^y)+2.(x&y)", "+.+xy/+xy//xy/");
+
+    // layer by layer
+    test_abstract_expr("(((a^b)+2.(a&b))^c)+2.(((a^b)+2.(a&b))&c)", "+abc/");
+    test_abstract_expr("((x^y)+2.(x&y))^z", "^+xy/z/");
+
+    // not shorter once substituted back
+    test_abstract_expr("-((a+b)^~c)+2", "+2-(^+ab/~c/)/");
+    test_abstract_expr("(x.y+1)|z", "|+1.xy//z/");
+}
+
+fn test_fixpoint_expr(expr: &str, max_passes: usize, exp: &str) -> FixpointReport {
+    let mut arena =
+        ArenaFactory::new_arena(&mut parse_rpn(expr.to_string()).unwrap()).expect("valid rpn");
+    let root = arena.root_node;
+    let report = simplify_fixpoint(&mut arena, root, max_passes).unwrap();
+    assert_eq!(arena.print().unwrap(), exp);
+    report
+}
+
+#[test]
+fn test_simplify_fixpoint() {
+    // operands of the outer mba are mba, bitwise once simplified
+    let expr = "(((a|b)-(a&b))^c)+2.(((a|b)-(a&b))&c)";
+    let report = test_fixpoint_expr(expr, 16, "+ab-(.2&ab/)/c/");
+    assert_eq!(
+        report,
+        FixpointReport {
+            num_simplified: 2,
+            num_passes: 2,
+            fixpoint: true
+        }
+    );
+    test_simplify_expr(expr, "+^^ab/c/.2&^ab/c///");
+
+    test_fixpoint_expr("(((a^b)+2.(a&b))^c)+2.(((a^b)+2.(a&b))&c)", 16, "+abc/");
+    test_fixpoint_expr("((x|y)-(x&y)).((x|y)-(x&y))", 16, ".^xy/^xy//");
+    assert!(test_fixpoint_expr("x+y", 16, "+xy/").fixpoint);
+
+    // pass cap
+    let report = test_fixpoint_expr(expr, 1, "+ab-(.2&ab/)/c/");
+    assert!(!report.fixpoint);
+    assert_eq!(report.num_passes, 1);
 }
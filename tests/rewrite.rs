@@ -0,0 +1,83 @@
+use dobf::equiv::{check_arenas, EquivResult};
+use dobf::error::{Error, Result};
+use dobf::expr::arena::Arena;
+use dobf::expr::factory::ArenaFactory;
+use dobf::parser::parse_rpn;
+use dobf::rewrite::{mba_rules, rewrite, Rule};
+
+fn new_arena(expr: &str, width: u8) -> Result<Arena> {
+    ArenaFactory::new_arena_width(&mut parse_rpn(expr.to_string())?, width)
+}
+
+// Print of the rewritten expression, with the rules fired
+fn rewrite_expr(expr: &str, rules: &[Rule]) -> Result<(String, Vec<String>)> {
+    let mut arena = new_arena(expr, 32)?;
+    let root = arena.root_node;
+    let report = rewrite(&mut arena, root, rules)?;
+    assert!(report.fixpoint);
+    Ok((arena.print()?, report.fired))
+}
+
+#[test]
+fn test_mba_rules_sound() -> Result<()> {
+    for rule in mba_rules()? {
+        let res = check_arenas(&new_arena(rule.lhs(), 8)?, &new_arena(rule.rhs(), 8)?)?;
+        assert_eq!(res, EquivResult::Exhaustive(), "rule {}", rule.name());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_rewrite() -> Result<()> {
+    let rules = mba_rules()?;
+    let (res, fired) = rewrite_expr("(x^y)+2.(x&y)", &rules)?;
+    assert_eq!(res, "+xy/");
+    assert_eq!(fired, vec!["xor-and-add"]);
+
+    // commutativity
+    assert_eq!(rewrite_expr("2.(y&x)+(y^x)", &rules)?.0, "+yx/");
+    assert_eq!(rewrite_expr("(x|~y)-(~y&x)", &rules)?.0, "^~yx/");
+
+    // part of the terms, the others are kept
+    assert_eq!(rewrite_expr("(x^y)+z+2.(x&y)", &rules)?.0, "+xyz/");
+    assert_eq!(rewrite_expr("x+y-(x&y)+5", &rules)?.0, "+5|xy//");
+
+    // sign of the node kept above the rewritten terms
+    assert_eq!(rewrite_expr("-((x|y)-(x&y))", &rules)?.0, "-(^xy)/");
+
+    // nested, up to a fixpoint
+    let (res, fired) = rewrite_expr("(x|y)+(x&y)-y", &rules)?;
+    assert_eq!(res, "x");
+    assert_eq!(fired, vec!["or-and-add", "add-sub"]);
+    let (res, fired) = rewrite_expr("((x^y)+2.(x&y))^((x|y)-(x&y))", &rules)?;
+    assert_eq!(res, "^+xy/^xy//");
+    assert_eq!(fired.len(), 2);
+
+    // a variable is bound to the same subterm
+    let (res, fired) = rewrite_expr("(x^y)+2.(x&z)", &rules)?;
+    assert_eq!(res, "+^xy/.2&xz///");
+    assert!(fired.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_custom_rules() -> Result<()> {
+    let rules = vec![Rule::new("neg-not", "-~a", "a+1")?];
+    assert_eq!(rewrite_expr("(-~x)^y", &rules)?.0, "^+1x/y/");
+
+    assert_eq!(
+        Rule::new("unbound", "a+b", "a+c").err(),
+        Some(Error::UnboundPatternVar("c".to_string()))
+    );
+
+    // no fixpoint, the rewrites stop after a while
+    let rules = vec![Rule::new("swap", "a+b", "b+a")?];
+    let mut arena = new_arena("x+y", 32)?;
+    let root = arena.root_node;
+    let report = rewrite(&mut arena, root, &rules)?;
+    assert!(!report.fixpoint);
+    assert_eq!(arena.print()?, "+xy/");
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use dobf::error::{ArenaError, Error};
+use dobf::expr::arena::Arena;
+use dobf::reduce::TruthTable;
+use dobf::synth::{generate_table3, lookup3, synthesize, BoolExpr, Cost};
+
+fn synth_rows(rows: &[u8]) -> Option<BoolExpr> {
+    synth_rows_cost(rows, Cost::Nodes)
+}
+
+fn synth_rows_cost(rows: &[u8], cost: Cost) -> Option<BoolExpr> {
+    let tt = TruthTable::from_rows(rows).unwrap();
+    let res = synthesize(&tt, cost);
+    if let Some(e) = &res {
+        assert_eq!(e.truth_table(tt.num_vars()), Ok(tt.clone()));
+    }
+    res
+}
+
+#[test]
+fn test_synth_const() {
+    assert_eq!(synth_rows(&[0, 0, 0, 0]), None);
+    assert_eq!(synth_rows(&[1, 1]), None);
+}
+
+#[test]
+fn test_synth_small() {
+    assert_eq!(synth_rows(&[0, 1]), Some(BoolExpr::Var(0)));
+    assert_eq!(synth_rows(&[0, 0, 1, 1]), Some(BoolExpr::Var(1)));
+
+    // x ^ y
+    assert_eq!(synth_rows(&[0, 1, 1, 0]).map(|e| e.cost()), Some(1));
+    // ~(x | y)
+    assert_eq!(synth_rows(&[1, 0, 0, 0]).map(|e| e.cost()), Some(2));
+    // x ^ y ^ z
+    assert_eq!(
+        synth_rows(&[0, 1, 1, 0, 1, 0, 0, 1]).map(|e| e.cost()),
+        Some(2)
+    );
+    // majority: (x & y) | (z & (x ^ y))
+    assert_eq!(
+        synth_rows(&[0, 0, 0, 1, 0, 1, 1, 1]).map(|e| e.cost()),
+        Some(4)
+    );
+}
+
+#[test]
+fn test_synth_all_3_vars() {
+    for f in 1..255u8 {
+        let rows: Vec<u8> = (0..8).map(|i| (f >> i) & 1).collect();
+        assert!(synth_rows(&rows).is_some());
+    }
+}
+
+#[test]
+fn test_synth_many_vars() {
+    // x0 ^ x1 ^ ... ^ x7
+    let rows: Vec<u8> = (0..256u32).map(|i| (i.count_ones() % 2) as u8).collect();
+    assert_eq!(synth_rows(&rows).map(|e| e.cost()), Some(7));
+
+    // (x0 & x1 & x2 & x3) | (x4 & x5)
+    let rows: Vec<u8> = (0..64u32)
+        .map(|i| ((i & 0xf == 0xf) || (i & 0x30 == 0x30)) as u8)
+        .collect();
+    assert!(synth_rows(&rows).is_some());
+
+    // no structure, still correct
+    let rows: Vec<u8> = (0..1024u32)
+        .map(|i| (i.wrapping_mul(0x9e37_79b9) >> 31) as u8)
+        .collect();
+    assert!(synth_rows(&rows).is_some());
+}
+
+#[test]
+fn test_synth_cost() {
+    // maj(x, y, z) is looked up in the table of the cost
+    let rows: Vec<u8> = (0..8).map(|i| (0xe8 >> i) & 1).collect();
+    for cost in [Cost::Nodes, Cost::Depth] {
+        assert_eq!(synth_rows_cost(&rows, cost), lookup3(0xe8, cost));
+    }
+
+    // t & maj(x, y, z), through the Shannon decomposition
+    let rows: Vec<u8> = (0..16).map(|i| (i >> 3) & (0xe8 >> (i & 7)) & 1).collect();
+    for cost in [Cost::Nodes, Cost::Depth] {
+        let e = synth_rows_cost(&rows, cost).unwrap();
+        assert_eq!((e.cost(), e.depth()), (5, 4));
+    }
+}
+
+#[test]
+fn test_synth_insert() -> Result<(), Error> {
+    let mut arena = Arena::new();
+    // x ^ ~(y | z)
+    let e = synth_rows(&[1, 0, 0, 1, 0, 1, 0, 1]).unwrap();
+    let vars: Vec<usize> = ["x", "y", "z"].iter().map(|v| arena.intern(v)).collect();
+    arena.root_node = e.insert(&mut arena, &vars)?;
+    assert!(arena.is_bitwise(arena.root_node)?);
+    assert_eq!(arena.gather_vars(arena.root_node)?.len(), 3);
+
+    // position out of the variables
+    assert_eq!(
+        BoolExpr::Var(3).insert(&mut arena, &vars),
+        Err(Error::Arena(ArenaError::UnknownVar(3)))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_table3() {
+    let (nodes, depth) = (generate_table3(Cost::Nodes), generate_table3(Cost::Depth));
+    for f in 0..=255u8 {
+        let rows: Vec<u8> = (0..8).map(|i| (f >> i) & 1).collect();
+        let tt = TruthTable::from_rows(&rows).unwrap();
+
+        // the checked-in table is the generated one
+        assert_eq!(lookup3(f, Cost::Nodes), nodes[f as usize]);
+        assert_eq!(lookup3(f, Cost::Depth), depth[f as usize]);
+        match (&nodes[f as usize], &depth[f as usize]) {
+            (Some(e_n), Some(e_d)) => {
+                assert_eq!(e_n.truth_table(3), Ok(tt.clone()));
+                assert_eq!(e_d.truth_table(3), Ok(tt));
+                assert!(e_n.cost() <= e_d.cost());
+                assert!(e_d.depth() <= e_n.depth());
+                assert_eq!(
+                    BoolExpr::from_postfix(&e_n.to_postfix()).as_ref(),
+                    Some(e_n)
+                );
+            }
+            (None, None) => assert!(f == 0 || f == 0xff),
+            _ => panic!("function {:#x} in one table only", f),
+        }
+    }
+
+    // majority: (x & y) | (z & (x ^ y))
+    assert_eq!(lookup3(0xe8, Cost::Nodes).map(|e| e.cost()), Some(4));
+    assert_eq!(lookup3(0xe8, Cost::Depth).map(|e| e.depth()), Some(3));
+    // ~(x | y | z)
+    assert_eq!(
+        lookup3(0x01, Cost::Nodes).map(|e| e.to_postfix()),
+        Some("abc||~".to_string())
+    );
+    assert_eq!(BoolExpr::from_postfix("ab"), None);
+}